@@ -0,0 +1,113 @@
+// 按字节搜索：用于非UTF-8文本和二进制文件，
+// 搜索结果是匹配在文件中的字节偏移量，而不是行
+
+// 每行十六进制转储显示的字节数
+const ROW: usize = 16;
+
+// 把查询字符串转换成字节序列。
+// \xNN 表示一个十六进制字节，\\ 表示反斜杠，其余字符按UTF-8编码。
+// 例如 "\x7fELF" 会得到 [0x7f, b'E', b'L', b'F']。
+pub fn parse_query(query: &str) -> Result<Vec<u8>, &'static str> {
+    let mut bytes = Vec::new();
+    let mut chars = query.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+
+        match chars.next() {
+            Some('\\') => bytes.push(b'\\'),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                // from_str_radix 还接受 + 号，这里要求正好两个十六进制数字
+                if hex.len() != 2 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err("\\x must be followed by two hex digits");
+                }
+                bytes.push(u8::from_str_radix(&hex, 16).unwrap());
+            }
+            _ => return Err("unsupported escape in binary query"),
+        }
+    }
+
+    if bytes.is_empty() {
+        return Err("binary query must not be empty");
+    }
+
+    Ok(bytes)
+}
+
+// 返回needle在contents中每次（不重叠）出现的起始偏移量
+pub fn search(needle: &[u8], contents: &[u8]) -> Vec<usize> {
+    find_all(needle, contents, |a, b| a == b)
+}
+
+// 只忽略ASCII字母的大小写，其余字节必须完全相同
+pub fn search_case_insensitive(needle: &[u8], contents: &[u8]) -> Vec<usize> {
+    find_all(needle, contents, |a, b| a.eq_ignore_ascii_case(&b))
+}
+
+fn find_all<F>(needle: &[u8], contents: &[u8], eq: F) -> Vec<usize>
+where
+    F: Fn(u8, u8) -> bool,
+{
+    let mut results = Vec::new();
+    if needle.is_empty() {
+        return results;
+    }
+
+    let mut i = 0;
+    while i + needle.len() <= contents.len() {
+        let window = &contents[i..i + needle.len()];
+        if window.iter().zip(needle).all(|(&a, &b)| eq(a, b)) {
+            results.push(i);
+            i += needle.len();
+        } else {
+            i += 1;
+        }
+    }
+
+    results
+}
+
+// 以 xxd 的格式输出覆盖 [offset, offset + len) 的所有行
+pub fn hex_dump(contents: &[u8], offset: usize, len: usize) -> String {
+    let first = offset / ROW * ROW;
+    let last = (offset + len).min(contents.len());
+    let mut dump = String::new();
+
+    let mut row = first;
+    while row < last {
+        let bytes = &contents[row..(row + ROW).min(contents.len())];
+
+        let mut hex = String::new();
+        for i in 0..ROW {
+            // 每8个字节之间多空一格，便于阅读
+            if i == ROW / 2 {
+                hex.push(' ');
+            }
+            match bytes.get(i) {
+                Some(b) => hex.push_str(&format!("{:02x} ", b)),
+                None => hex.push_str("   "),
+            }
+        }
+
+        let ascii: String = bytes
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+
+        dump.push_str(&format!("{:08x}  {} |{}|\n", row, hex, ascii));
+        row += ROW;
+    }
+
+    dump
+}
@@ -0,0 +1,334 @@
+// 命令行选项的唯一定义。
+// Config::new 按这张表解析参数，补全脚本和man手册也由它生成，三者不会出现不一致。
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opt {
+    ByteOffset,
+    Binary,
+    Encoding,
+    Field,
+    Column,
+    Delimiter,
+    GenerateMan,
+}
+
+pub struct OptSpec {
+    pub opt: Opt,
+    pub long: &'static str,
+    pub short: Option<char>,
+    // 需要取值的选项在帮助信息中显示的值名称
+    pub value_name: Option<&'static str>,
+    // 补全时可供选择的取值，空表示任意值
+    pub values: &'static [&'static str],
+    pub help: &'static str,
+}
+
+pub const OPTIONS: &[OptSpec] = &[
+    OptSpec {
+        opt: Opt::ByteOffset,
+        long: "byte-offset",
+        short: Some('b'),
+        value_name: None,
+        values: &[],
+        help: "Print the byte offset in the original file before each match",
+    },
+    OptSpec {
+        opt: Opt::Binary,
+        long: "binary",
+        short: Some('a'),
+        value_name: None,
+        values: &[],
+        help: "Search raw bytes; the query may contain \\xNN escapes",
+    },
+    OptSpec {
+        opt: Opt::Encoding,
+        long: "encoding",
+        short: None,
+        value_name: Some("ENCODING"),
+        values: &["utf-8", "utf-16le", "utf-16be", "latin1"],
+        help: "Decode the file with this encoding instead of sniffing the BOM",
+    },
+    OptSpec {
+        opt: Opt::Field,
+        long: "field",
+        short: None,
+        value_name: Some("KEY"),
+        values: &[],
+        help: "Treat each line as JSON and match only this (dotted) key",
+    },
+    OptSpec {
+        opt: Opt::Column,
+        long: "column",
+        short: None,
+        value_name: Some("N"),
+        values: &[],
+        help: "Treat each line as CSV/TSV and match only the N-th column",
+    },
+    OptSpec {
+        opt: Opt::Delimiter,
+        long: "delimiter",
+        short: None,
+        value_name: Some("CHAR"),
+        values: &[",", ";", "tab"],
+        help: "Column delimiter for --column (default: tab for .tsv files, comma otherwise)",
+    },
+    OptSpec {
+        opt: Opt::GenerateMan,
+        long: "generate-man",
+        short: None,
+        value_name: None,
+        values: &[],
+        help: "Print a roff man page for minigrep and exit",
+    },
+];
+
+// 根据参数名（--long 或 -s）查找选项定义
+pub fn lookup(name: &str) -> Option<&'static OptSpec> {
+    if let Some(long) = name.strip_prefix("--") {
+        OPTIONS.iter().find(|spec| spec.long == long)
+    } else if let Some(short) = name.strip_prefix('-') {
+        let mut chars = short.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => OPTIONS.iter().find(|spec| spec.short == Some(c)),
+            _ => None,
+        }
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    pub const ALL: [Shell; 3] = [Shell::Bash, Shell::Zsh, Shell::Fish];
+
+    pub fn from_name(name: &str) -> Option<Shell> {
+        Shell::ALL.into_iter().find(|shell| shell.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Fish => "fish",
+        }
+    }
+}
+
+fn shell_names() -> String {
+    let names: Vec<&str> = Shell::ALL.iter().map(|shell| shell.name()).collect();
+    names.join(" ")
+}
+
+// 生成指定shell的补全脚本
+pub fn completions(shell: Shell) -> String {
+    match shell {
+        Shell::Bash => bash_completions(),
+        Shell::Zsh => zsh_completions(),
+        Shell::Fish => fish_completions(),
+    }
+}
+
+fn bash_completions() -> String {
+    let mut flags = Vec::new();
+    let mut cases = String::new();
+
+    for spec in OPTIONS {
+        if let Some(short) = spec.short {
+            flags.push(format!("-{}", short));
+        }
+        flags.push(format!("--{}", spec.long));
+
+        if spec.value_name.is_some() {
+            // 取值为任意内容的选项不做补全
+            let reply = if spec.values.is_empty() {
+                String::from("return")
+            } else {
+                format!(
+                    "COMPREPLY=( $(compgen -W \"{}\" -- \"${{cur}}\") ); return",
+                    spec.values.join(" ")
+                )
+            };
+            cases.push_str(&format!(
+                "        --{})\n            {}\n            ;;\n",
+                spec.long, reply
+            ));
+        }
+    }
+
+    format!(
+        r#"_minigrep() {{
+    local cur prev
+    cur="${{COMP_WORDS[COMP_CWORD]}}"
+    prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+
+    if [[ ${{COMP_WORDS[1]}} == completions ]]; then
+        if [[ ${{COMP_CWORD}} -eq 2 ]]; then
+            COMPREPLY=( $(compgen -W "{shells}" -- "${{cur}}") )
+        fi
+        return
+    fi
+
+    case "${{prev}}" in
+{cases}    esac
+
+    if [[ ${{cur}} == -* ]]; then
+        COMPREPLY=( $(compgen -W "{flags}" -- "${{cur}}") )
+    elif [[ ${{COMP_CWORD}} -eq 1 ]]; then
+        COMPREPLY=( $(compgen -W "completions" -- "${{cur}}") )
+    else
+        COMPREPLY=( $(compgen -f -- "${{cur}}") )
+    fi
+}}
+
+complete -o filenames -F _minigrep minigrep
+"#,
+        shells = shell_names(),
+        cases = cases,
+        flags = flags.join(" "),
+    )
+}
+
+fn zsh_completions() -> String {
+    let mut specs = String::new();
+
+    for spec in OPTIONS {
+        let help = spec
+            .help
+            .replace('\'', "'\\''")
+            .replace('[', "\\[")
+            .replace(']', "\\]");
+        // 带短选项时写成 '(-b --long)'{-b,--long}'[help]'，后面的引号重新打开
+        let names = match spec.short {
+            Some(short) => format!("'(-{0} --{1})'{{-{0},--{1}}}'", short, spec.long),
+            None if spec.value_name.is_some() => format!("'--{}=", spec.long),
+            None => format!("'--{}", spec.long),
+        };
+
+        let value = match spec.value_name {
+            Some(name) if spec.values.is_empty() => format!(":{}: ", name.to_lowercase()),
+            Some(name) => format!(":{}:({})", name.to_lowercase(), spec.values.join(" ")),
+            None => String::new(),
+        };
+
+        specs.push_str(&format!("        {}[{}]{}' \\\n", names, help, value));
+    }
+
+    format!(
+        r#"#compdef minigrep
+
+_minigrep() {{
+    if [[ $words[2] == completions ]]; then
+        _arguments '2:shell:({shells})'
+        return
+    fi
+
+    _arguments -s \
+{specs}        '1:query:' \
+        '2:file:_files'
+}}
+
+_minigrep "$@"
+"#,
+        shells = shell_names(),
+        specs = specs,
+    )
+}
+
+fn fish_completions() -> String {
+    let mut script = format!(
+        "complete -c minigrep -n '__fish_use_subcommand' -a completions -d 'Print a shell completion script'\n\
+         complete -c minigrep -n '__fish_seen_subcommand_from completions' -x -a '{}'\n",
+        shell_names()
+    );
+
+    for spec in OPTIONS {
+        let mut line = String::from("complete -c minigrep");
+        if let Some(short) = spec.short {
+            line.push_str(&format!(" -s {}", short));
+        }
+        line.push_str(&format!(" -l {}", spec.long));
+        if spec.value_name.is_some() {
+            // -r 表示该选项需要一个值
+            line.push_str(" -r");
+            if !spec.values.is_empty() {
+                line.push_str(&format!(" -f -a '{}'", spec.values.join(" ")));
+            }
+        }
+        line.push_str(&format!(" -d '{}'\n", spec.help.replace('\'', "\\'")));
+        script.push_str(&line);
+    }
+
+    script
+}
+
+// roff 中的 - 和 \ 需要转义
+fn roff(text: &str) -> String {
+    text.replace('\\', "\\e").replace('-', "\\-")
+}
+
+// 生成 man(1) 格式的手册页
+pub fn man_page() -> String {
+    let mut page = format!(
+        r#".TH MINIGREP 1 "" "minigrep {version}" "User Commands"
+.SH NAME
+minigrep \- print lines of a file that contain a query
+.SH SYNOPSIS
+.B minigrep
+[\fIOPTIONS\fR] \fIQUERY\fR \fIFILE\fR
+.br
+.B minigrep completions
+\fISHELL\fR
+.SH DESCRIPTION
+.B minigrep
+prints every line of \fIFILE\fR that contains \fIQUERY\fR.
+UTF\-16 and UTF\-8 files with a byte order mark are transcoded automatically.
+An argument of \fB\-\-\fR ends option parsing, so queries may start with a dash.
+.SH OPTIONS
+"#,
+        version = env!("CARGO_PKG_VERSION"),
+    );
+
+    for spec in OPTIONS {
+        page.push_str(".TP\n");
+        if let Some(short) = spec.short {
+            page.push_str(&format!("\\fB\\-{}\\fR, ", short));
+        }
+        page.push_str(&format!("\\fB\\-\\-{}\\fR", roff(spec.long)));
+        if let Some(name) = spec.value_name {
+            page.push_str(&format!(" \\fI{}\\fR", name));
+        }
+        page.push('\n');
+        page.push_str(&roff(spec.help));
+        page.push('.');
+        if !spec.values.is_empty() {
+            page.push_str(&format!(
+                "\nPossible values: {}.",
+                roff(&spec.values.join(", "))
+            ));
+        }
+        page.push('\n');
+    }
+
+    page.push_str(&format!(
+        r#".SH COMMANDS
+.TP
+\fBcompletions\fR \fISHELL\fR
+Print a completion script for \fISHELL\fR ({shells}).
+.SH ENVIRONMENT
+.TP
+.B CASE_SENSITIVE
+When set, matching ignores case.
+.SH EXIT STATUS
+0 on success, 1 if the arguments are invalid or the file cannot be read.
+"#,
+        shells = shell_names().replace(' ', ", "),
+    ));
+
+    page
+}
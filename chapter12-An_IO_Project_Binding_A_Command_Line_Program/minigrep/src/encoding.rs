@@ -0,0 +1,179 @@
+use std::error::Error;
+use std::fmt;
+
+// minigrep 能够识别并转码为 UTF-8 的文本编码
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+}
+
+impl Encoding {
+    // 解析 --encoding 参数的取值，大小写不敏感
+    pub fn from_label(label: &str) -> Option<Encoding> {
+        match label.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Some(Encoding::Utf8),
+            "utf-16le" | "utf16le" => Some(Encoding::Utf16Le),
+            "utf-16be" | "utf16be" => Some(Encoding::Utf16Be),
+            "latin1" | "latin-1" | "iso-8859-1" => Some(Encoding::Latin1),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "utf-8",
+            Encoding::Utf16Le => "utf-16le",
+            Encoding::Utf16Be => "utf-16be",
+            Encoding::Latin1 => "latin1",
+        }
+    }
+
+    // 该编码对应的字节顺序标记，Latin-1 没有 BOM
+    fn bom(&self) -> &'static [u8] {
+        match self {
+            Encoding::Utf8 => &[0xEF, 0xBB, 0xBF],
+            Encoding::Utf16Le => &[0xFF, 0xFE],
+            Encoding::Utf16Be => &[0xFE, 0xFF],
+            Encoding::Latin1 => &[],
+        }
+    }
+}
+
+// 根据文件开头的 BOM 嗅探编码，返回编码以及 BOM 占用的字节数
+pub fn detect_bom(bytes: &[u8]) -> Option<(Encoding, usize)> {
+    [Encoding::Utf8, Encoding::Utf16Le, Encoding::Utf16Be]
+        .into_iter()
+        .find(|encoding| bytes.starts_with(encoding.bom()))
+        .map(|encoding| (encoding, encoding.bom().len()))
+}
+
+#[derive(Debug)]
+pub struct DecodeError {
+    encoding: Encoding,
+    offset: usize,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invalid {} data at byte offset {}",
+            self.encoding.label(),
+            self.offset
+        )
+    }
+}
+
+impl Error for DecodeError {}
+
+// 转码后的 UTF-8 文本，同时记住每个字符在原始字节中的位置
+pub struct Decoded {
+    pub text: String,
+    pub encoding: Encoding,
+    // BOM 的长度，UTF-8 文本的偏移量只需要加上它
+    base: usize,
+    // (text 中的字节下标, 原始字节中的下标)，只在发生转码时记录
+    offsets: Vec<(usize, usize)>,
+    // 原始数据的总长度
+    end: usize,
+}
+
+impl Decoded {
+    // 把 text 中的字节下标换算成原始文件中的字节偏移量
+    pub fn source_offset(&self, index: usize) -> usize {
+        if self.offsets.is_empty() {
+            return self.base + index;
+        }
+
+        match self.offsets.binary_search_by_key(&index, |&(text, _)| text) {
+            Ok(i) => self.offsets[i].1,
+            // 下标落在 text 末尾：返回原始数据的末尾
+            Err(i) if i == self.offsets.len() => self.end,
+            Err(i) => self.offsets[i].1,
+        }
+    }
+}
+
+// 将原始字节解码为 UTF-8。
+// 指定了 encoding 时按指定编码解码，否则根据 BOM 判断，没有 BOM 时按 UTF-8 处理。
+pub fn decode(bytes: &[u8], encoding: Option<Encoding>) -> Result<Decoded, DecodeError> {
+    let (encoding, base) = match (encoding, detect_bom(bytes)) {
+        // 指定的编码与 BOM 一致时同样跳过 BOM
+        (Some(forced), Some((sniffed, len))) if forced == sniffed => (forced, len),
+        (Some(forced), _) => (forced, 0),
+        (None, Some(sniffed)) => sniffed,
+        (None, None) => (Encoding::Utf8, 0),
+    };
+    let body = &bytes[base..];
+
+    match encoding {
+        Encoding::Utf8 => match std::str::from_utf8(body) {
+            Ok(text) => Ok(Decoded {
+                text: text.to_string(),
+                encoding,
+                base,
+                offsets: Vec::new(),
+                end: bytes.len(),
+            }),
+            Err(e) => Err(DecodeError {
+                encoding,
+                offset: base + e.valid_up_to(),
+            }),
+        },
+        Encoding::Latin1 => {
+            // Latin-1 的每个字节都直接对应 U+0000..=U+00FF
+            let chars = body.iter().enumerate().map(|(i, &b)| (base + i, b as char));
+            Ok(collect(chars, encoding, base, bytes.len()))
+        }
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            if !body.len().is_multiple_of(2) {
+                return Err(DecodeError {
+                    encoding,
+                    offset: bytes.len() - 1,
+                });
+            }
+
+            let units = body.chunks_exact(2).map(|pair| match encoding {
+                Encoding::Utf16Le => u16::from_le_bytes([pair[0], pair[1]]),
+                _ => u16::from_be_bytes([pair[0], pair[1]]),
+            });
+
+            // 代理对占两个码元，按已消耗的码元数推算原始偏移量；
+            // 不成对的代理项替换为 U+FFFD
+            let mut consumed = 0;
+            let chars = char::decode_utf16(units).map(|r| {
+                let start = base + consumed * 2;
+                let c = r.unwrap_or(char::REPLACEMENT_CHARACTER);
+                consumed += if (c as u32) > 0xFFFF { 2 } else { 1 };
+                (start, c)
+            });
+            Ok(collect(chars, encoding, base, bytes.len()))
+        }
+    }
+}
+
+fn collect(
+    chars: impl Iterator<Item = (usize, char)>,
+    encoding: Encoding,
+    base: usize,
+    end: usize,
+) -> Decoded {
+    let mut text = String::new();
+    let mut offsets = Vec::new();
+
+    for (source, c) in chars {
+        offsets.push((text.len(), source));
+        text.push(c);
+    }
+
+    Decoded {
+        text,
+        encoding,
+        base,
+        offsets,
+        end,
+    }
+}
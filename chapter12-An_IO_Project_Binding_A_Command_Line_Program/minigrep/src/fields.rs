@@ -0,0 +1,357 @@
+// 按字段搜索：把每一行当作一条记录解析，只在选中的字段里匹配query，
+// 匹配成功时返回整条记录
+
+// 要匹配的字段
+#[derive(Debug, Clone, PartialEq)]
+pub enum Field {
+    // JSON Lines 中的键，可以用 . 访问嵌套对象，如 "http.status"
+    Json(String),
+    // CSV/TSV 中的第 index 列（从 1 开始计数）
+    Column { index: usize, delimiter: char },
+}
+
+impl Field {
+    // 从一行记录中取出字段的文本；记录无法解析或字段不存在时返回None
+    pub fn extract(&self, record: &str) -> Option<String> {
+        match self {
+            Field::Json(path) => {
+                let mut value = json::parse(record)?;
+                for key in path.split('.') {
+                    value = value.take(key)?;
+                }
+                Some(value.into_text())
+            }
+            Field::Column { index, delimiter } => split_record(record, *delimiter)
+                .into_iter()
+                .nth(index.checked_sub(1)?),
+        }
+    }
+}
+
+pub fn search<'a>(query: &str, contents: &'a str, field: &Field) -> Vec<&'a str> {
+    contents
+        .lines()
+        .filter(|line| match field.extract(line) {
+            Some(text) => text.contains(query),
+            None => false,
+        })
+        .collect()
+}
+
+pub fn search_case_insensitive<'a>(query: &str, contents: &'a str, field: &Field) -> Vec<&'a str> {
+    let query = query.to_lowercase();
+
+    contents
+        .lines()
+        .filter(|line| match field.extract(line) {
+            Some(text) => text.to_lowercase().contains(&query),
+            None => false,
+        })
+        .collect()
+}
+
+// 按分隔符拆分一行CSV/TSV记录。
+// 双引号包裹的字段中可以出现分隔符，"" 表示一个字面量双引号。
+pub fn split_record(record: &str, delimiter: char) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut chars = record.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if quoted {
+            if c == '"' {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    current.push('"');
+                } else {
+                    quoted = false;
+                }
+            } else {
+                current.push(c);
+            }
+        } else if c == '"' {
+            quoted = true;
+        } else if c == delimiter {
+            fields.push(current);
+            current = String::new();
+        } else {
+            current.push(c);
+        }
+    }
+
+    fields.push(current);
+    fields
+}
+
+// 一个只够用来读取JSON Lines记录的小型JSON解析器
+mod json {
+    pub enum Value {
+        Null,
+        // 布尔值和数字按原样保留文本
+        Literal(String),
+        Str(String),
+        Array(Vec<Value>),
+        Object(Vec<(String, Value)>),
+    }
+
+    impl Value {
+        // 取出对象中键为key的值
+        pub fn take(self, key: &str) -> Option<Value> {
+            match self {
+                Value::Object(entries) => {
+                    entries.into_iter().find(|(k, _)| k == key).map(|(_, v)| v)
+                }
+                _ => None,
+            }
+        }
+
+        // 字段的可搜索文本：字符串取其内容，其他值转回JSON文本
+        pub fn into_text(self) -> String {
+            match self {
+                Value::Str(s) => s,
+                other => other.to_json(),
+            }
+        }
+
+        fn to_json(&self) -> String {
+            match self {
+                Value::Null => String::from("null"),
+                Value::Literal(s) => s.clone(),
+                Value::Str(s) => quote(s),
+                Value::Array(items) => {
+                    let items: Vec<String> = items.iter().map(|v| v.to_json()).collect();
+                    format!("[{}]", items.join(","))
+                }
+                Value::Object(entries) => {
+                    let entries: Vec<String> = entries
+                        .iter()
+                        .map(|(k, v)| format!("{}:{}", quote(k), v.to_json()))
+                        .collect();
+                    format!("{{{}}}", entries.join(","))
+                }
+            }
+        }
+    }
+
+    // JSON 字符串字面量。Rust 的 {:?} 会输出 \u{1f} 这类 JSON 不认识的转义
+    fn quote(s: &str) -> String {
+        let mut quoted = String::from("\"");
+        for c in s.chars() {
+            match c {
+                '"' => quoted.push_str("\\\""),
+                '\\' => quoted.push_str("\\\\"),
+                '\n' => quoted.push_str("\\n"),
+                '\r' => quoted.push_str("\\r"),
+                '\t' => quoted.push_str("\\t"),
+                '\u{8}' => quoted.push_str("\\b"),
+                '\u{c}' => quoted.push_str("\\f"),
+                c if c < ' ' => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+                c => quoted.push(c),
+            }
+        }
+        quoted.push('"');
+        quoted
+    }
+
+    pub fn parse(text: &str) -> Option<Value> {
+        let mut parser = Parser {
+            chars: text.chars().collect(),
+            pos: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        // 值后面不允许再有其他内容
+        if parser.pos == parser.chars.len() {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    struct Parser {
+        chars: Vec<char>,
+        pos: usize,
+    }
+
+    impl Parser {
+        fn peek(&self) -> Option<char> {
+            self.chars.get(self.pos).copied()
+        }
+
+        fn bump(&mut self) -> Option<char> {
+            let c = self.peek()?;
+            self.pos += 1;
+            Some(c)
+        }
+
+        fn expect(&mut self, expected: char) -> Option<()> {
+            if self.bump()? == expected {
+                Some(())
+            } else {
+                None
+            }
+        }
+
+        fn skip_whitespace(&mut self) {
+            while matches!(self.peek(), Some(c) if c.is_whitespace()) {
+                self.pos += 1;
+            }
+        }
+
+        fn value(&mut self) -> Option<Value> {
+            self.skip_whitespace();
+            match self.peek()? {
+                '{' => self.object(),
+                '[' => self.array(),
+                '"' => self.string().map(Value::Str),
+                _ => self.literal(),
+            }
+        }
+
+        fn object(&mut self) -> Option<Value> {
+            self.expect('{')?;
+            let mut entries = Vec::new();
+
+            self.skip_whitespace();
+            if self.peek()? == '}' {
+                self.pos += 1;
+                return Some(Value::Object(entries));
+            }
+
+            loop {
+                self.skip_whitespace();
+                let key = self.string()?;
+                self.skip_whitespace();
+                self.expect(':')?;
+                let value = self.value()?;
+                entries.push((key, value));
+
+                self.skip_whitespace();
+                match self.bump()? {
+                    ',' => continue,
+                    '}' => return Some(Value::Object(entries)),
+                    _ => return None,
+                }
+            }
+        }
+
+        fn array(&mut self) -> Option<Value> {
+            self.expect('[')?;
+            let mut items = Vec::new();
+
+            self.skip_whitespace();
+            if self.peek()? == ']' {
+                self.pos += 1;
+                return Some(Value::Array(items));
+            }
+
+            loop {
+                items.push(self.value()?);
+                self.skip_whitespace();
+                match self.bump()? {
+                    ',' => continue,
+                    ']' => return Some(Value::Array(items)),
+                    _ => return None,
+                }
+            }
+        }
+
+        fn string(&mut self) -> Option<String> {
+            self.expect('"')?;
+            let mut s = String::new();
+
+            loop {
+                match self.bump()? {
+                    '"' => return Some(s),
+                    '\\' => match self.bump()? {
+                        'n' => s.push('\n'),
+                        't' => s.push('\t'),
+                        'r' => s.push('\r'),
+                        'b' => s.push('\u{8}'),
+                        'f' => s.push('\u{c}'),
+                        'u' => s.push(self.unicode_escape()?),
+                        c @ ('"' | '\\' | '/') => s.push(c),
+                        // JSON 只有以上几种转义
+                        _ => return None,
+                    },
+                    // 控制字符必须转义
+                    c if c < ' ' => return None,
+                    c => s.push(c),
+                }
+            }
+        }
+
+        // \uXXXX，包括用两个转义表示的代理对
+        fn unicode_escape(&mut self) -> Option<char> {
+            let high = self.hex4()?;
+            if (0xD800..0xDC00).contains(&high) {
+                self.expect('\\')?;
+                self.expect('u')?;
+                let low = self.hex4()?;
+                // 高位代理后面必须紧跟低位代理
+                if !(0xDC00..=0xDFFF).contains(&low) {
+                    return None;
+                }
+                char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00))
+            } else {
+                char::from_u32(high)
+            }
+        }
+
+        fn hex4(&mut self) -> Option<u32> {
+            let mut code = 0;
+            for _ in 0..4 {
+                code = code * 16 + self.bump()?.to_digit(16)?;
+            }
+            Some(code)
+        }
+
+        // null、true、false 和数字
+        fn literal(&mut self) -> Option<Value> {
+            let start = self.pos;
+            while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || "+-.".contains(c)) {
+                self.pos += 1;
+            }
+
+            let text: String = self.chars[start..self.pos].iter().collect();
+            match text.as_str() {
+                "null" => Some(Value::Null),
+                "true" | "false" => Some(Value::Literal(text)),
+                _ if is_number(&text) => Some(Value::Literal(text)),
+                _ => None,
+            }
+        }
+    }
+
+    // JSON的数字：-?(0|[1-9][0-9]*)(.[0-9]+)?([eE][+-]?[0-9]+)?
+    // 不能直接用 f64::from_str，它还接受 inf、NaN、+1 和 .5 等写法
+    fn is_number(text: &str) -> bool {
+        fn digits(s: &str) -> (&str, &str) {
+            let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+            s.split_at(end)
+        }
+
+        let rest = text.strip_prefix('-').unwrap_or(text);
+        let (int, mut rest) = digits(rest);
+        if int.is_empty() || (int.len() > 1 && int.starts_with('0')) {
+            return false;
+        }
+        if let Some(after) = rest.strip_prefix('.') {
+            let (frac, after) = digits(after);
+            if frac.is_empty() {
+                return false;
+            }
+            rest = after;
+        }
+        if let Some(after) = rest.strip_prefix(['e', 'E']) {
+            let after = after.strip_prefix(['+', '-']).unwrap_or(after);
+            let (exp, after) = digits(after);
+            if exp.is_empty() {
+                return false;
+            }
+            rest = after;
+        }
+        rest.is_empty()
+    }
+}
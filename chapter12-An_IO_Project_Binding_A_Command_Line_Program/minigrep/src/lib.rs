@@ -1,48 +1,266 @@
+use std::env;
 use std::error::Error;
 use std::fs;
-use std::env;
+use std::path::Path;
+
+pub mod binary;
+pub mod cli;
+pub mod encoding;
+pub mod fields;
+
+use cli::{Opt, Shell};
+use encoding::Encoding;
+use fields::Field;
+
+// minigrep 可以执行的操作
+pub enum Command {
+    Search(Config),
+    // minigrep completions <shell>
+    Completions(Shell),
+    // minigrep --generate-man
+    Man,
+}
+
+impl Command {
+    pub fn parse(args: &[String]) -> Result<Command, String> {
+        // 第二个参数是选项或已有的文件时，"minigrep completions file.txt" 仍然是搜索 completions 这个词；
+        // 否则是子命令，不支持的shell和多余的参数直接报错，不会被当作文件名去搜索
+        if let [_, name, shell, rest @ ..] = args {
+            if name == "completions" && !shell.starts_with('-') {
+                match Shell::from_name(shell) {
+                    Some(shell) if rest.is_empty() => return Ok(Command::Completions(shell)),
+                    _ if Path::new(shell).exists() => {}
+                    Some(_) => {
+                        return Err(format!("unexpected argument for completions: {}", rest[0]))
+                    }
+                    None => {
+                        let names: Vec<_> = Shell::ALL.iter().map(Shell::name).collect();
+                        return Err(format!(
+                            "unknown shell: {} (expected {})",
+                            shell,
+                            names.join(", ")
+                        ));
+                    }
+                }
+            }
+        }
+
+        // --generate-man 只能单独使用，和其他参数一起出现时由 Config::new 报错
+        if let [_, option] = args {
+            if option == "--generate-man" {
+                return Ok(Command::Man);
+            }
+        }
+
+        Config::new(args).map(Command::Search).map_err(String::from)
+    }
+}
 
 pub struct Config {
     pub query: String,
     pub filename: String,
     pub case_sensitive: bool,
+    // None 表示根据 BOM 自动识别
+    pub encoding: Option<Encoding>,
+    // 在每行前输出该行在原始文件中的字节偏移量
+    pub byte_offset: bool,
+    // 只在记录的某个字段中匹配，None 表示匹配整行
+    pub field: Option<Field>,
+    // 按原始字节搜索，适用于二进制文件
+    pub binary: bool,
 }
 
 impl Config {
     // &'static str 字符串字面量的类型，错误提示信息类型
     pub fn new(args: &[String]) -> Result<Config, &'static str> {
-        if args.len() < 3 {
+        let mut positional = Vec::new();
+        let mut encoding = None;
+        let mut byte_offset = false;
+        let mut binary = false;
+        let mut json_field = None;
+        let mut column = None;
+        let mut delimiter = None;
+
+        let mut options_done = false;
+
+        // 跳过args[0]，即当前执行的二进制文件名称
+        let mut iter = args.iter().skip(1);
+        while let Some(arg) = iter.next() {
+            // -- 之后的参数都是位置参数，这样查询字符串也可以以 - 开头
+            if options_done || !arg.starts_with('-') || arg == "-" {
+                positional.push(arg.clone());
+                continue;
+            }
+            if arg == "--" {
+                options_done = true;
+                continue;
+            }
+
+            // 同时支持 --name value 和 --name=value 两种写法
+            let (name, inline) = match arg.split_once('=') {
+                Some((name, value)) if name.starts_with("--") => (name, Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            };
+
+            // 所有选项都定义在 cli::OPTIONS 中
+            let spec = cli::lookup(name).ok_or("unknown option")?;
+            if spec.value_name.is_none() && inline.is_some() {
+                return Err("option does not take a value");
+            }
+
+            match spec.opt {
+                Opt::ByteOffset => byte_offset = true,
+                Opt::Binary => binary = true,
+                Opt::Encoding => {
+                    let label = option_value(inline, &mut iter)?;
+                    encoding = Some(Encoding::from_label(&label).ok_or("unknown encoding")?);
+                }
+                Opt::Field => json_field = Some(option_value(inline, &mut iter)?),
+                Opt::Column => {
+                    let index = option_value(inline, &mut iter)?
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|&index| index > 0)
+                        .ok_or("--column expects a positive number")?;
+                    column = Some(index);
+                }
+                Opt::Delimiter => {
+                    let value = option_value(inline, &mut iter)?;
+                    delimiter = Some(match value.as_str() {
+                        "tab" | "\\t" => '\t',
+                        _ => {
+                            let mut chars = value.chars();
+                            match (chars.next(), chars.next()) {
+                                (Some(c), None) => c,
+                                _ => return Err("--delimiter expects a single character"),
+                            }
+                        }
+                    });
+                }
+                // 由 Command::parse 处理，不能与搜索一起使用
+                Opt::GenerateMan => return Err("--generate-man cannot be combined with a search"),
+            }
+        }
+
+        if positional.len() < 2 {
             return Err("not enough arguments!");
         }
 
-        let query = args[1].clone();
-        let filename = args[2].clone();
+        let query = positional[0].clone();
+        let filename = positional[1].clone();
         // Result的is_err方法来检查结果是否为错误
         let case_sensitive = env::var("CASE_SENSITIVE").is_err();
 
-        Ok(Config { query, filename, case_sensitive })
+        if delimiter.is_some() && column.is_none() {
+            return Err("--delimiter requires --column");
+        }
+
+        let field = match (json_field, column) {
+            (Some(_), Some(_)) => return Err("--field and --column cannot be combined"),
+            (Some(path), None) => Some(Field::Json(path)),
+            (None, Some(index)) => {
+                // 没有指定分隔符时，.tsv 文件按制表符拆分，其余按逗号拆分
+                let default = if filename.ends_with(".tsv") {
+                    '\t'
+                } else {
+                    ','
+                };
+                let delimiter = delimiter.unwrap_or(default);
+                Some(Field::Column { index, delimiter })
+            }
+            (None, None) => None,
+        };
+
+        if binary && field.is_some() {
+            return Err("--binary cannot be combined with --field or --column");
+        }
+        // 按字节搜索时不解码文件，指定的编码不会生效
+        if binary && encoding.is_some() {
+            return Err("--binary cannot be combined with --encoding");
+        }
+
+        Ok(Config {
+            query,
+            filename,
+            case_sensitive,
+            encoding,
+            byte_offset,
+            field,
+            binary,
+        })
     }
 }
 
+// 取出选项的值：--name=value 中的value，或者紧跟在--name之后的参数
+fn option_value<'a>(
+    inline: Option<String>,
+    rest: &mut impl Iterator<Item = &'a String>,
+) -> Result<String, &'static str> {
+    inline
+        .or_else(|| rest.next().cloned())
+        .ok_or("option requires a value")
+}
+
 pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
     // Box<dyn Error>意味着函数会返回一个实现了Error trait的类型
     // ？运算符取代了expect
     // ?运算符可以将错误值返回给函数的调用者来进行处理
-    let contents = fs::read_to_string(config.filename)?;
+    // 读取原始字节后再转码，非UTF-8的文件（如UTF-16导出的日志）也能搜索
+    let bytes = fs::read(&config.filename)?;
 
-    let results = if config.case_sensitive {
-        search(&config.query, &contents)
-    } else {
-        search_case_insensitive(&config.query, &contents)
+    if config.binary {
+        return run_binary(&config, &bytes);
+    }
+
+    let decoded = encoding::decode(&bytes, config.encoding)?;
+    let contents = decoded.text.as_str();
+
+    let results = match (&config.field, config.case_sensitive) {
+        (Some(field), true) => fields::search(&config.query, contents, field),
+        (Some(field), false) => fields::search_case_insensitive(&config.query, contents, field),
+        (None, true) => search(&config.query, contents),
+        (None, false) => search_case_insensitive(&config.query, contents),
     };
 
     for line in results {
-        println!("{}", line);
+        if config.byte_offset {
+            // 偏移量按原始文件的字节计算，而不是转码后的UTF-8
+            let offset = decoded.source_offset(line_offset(contents, line));
+            println!("{}:{}", offset, line);
+        } else {
+            println!("{}", line);
+        }
     }
 
     Ok(())
 }
 
+// 二进制模式：默认输出覆盖每个匹配的十六进制转储，-b 时只输出偏移量
+fn run_binary(config: &Config, bytes: &[u8]) -> Result<(), Box<dyn Error>> {
+    let needle = binary::parse_query(&config.query)?;
+
+    let offsets = if config.case_sensitive {
+        binary::search(&needle, bytes)
+    } else {
+        binary::search_case_insensitive(&needle, bytes)
+    };
+
+    for offset in offsets {
+        if config.byte_offset {
+            println!("{}", offset);
+        } else {
+            print!("{}", binary::hex_dump(bytes, offset, needle.len()));
+        }
+    }
+
+    Ok(())
+}
+
+// search返回的每一行都是contents的切片，两者的指针之差就是该行的起始下标
+pub fn line_offset(contents: &str, line: &str) -> usize {
+    line.as_ptr() as usize - contents.as_ptr() as usize
+}
+
 // search函数的签名中需要一个显式生命周期'a，它被用来和contents参数与返回值一起使用。
 // 指定contents生命周期与返回值生命周期关联
 // 只有当切片引用的数据有效时，引用本身才是有效的
@@ -101,4 +319,175 @@ Trust me.";
             search_case_insensitive(query, contents)
         );
     }
+
+    #[test]
+    fn utf16_offsets_point_at_original_bytes() {
+        let mut bytes = vec![0xFF, 0xFE];
+        for unit in "héllo\nworld\n".encode_utf16() {
+            bytes.extend_from_slice(&unit.to_le_bytes());
+        }
+
+        let decoded = encoding::decode(&bytes, None).unwrap();
+        assert_eq!(Encoding::Utf16Le, decoded.encoding);
+        assert_eq!("héllo\nworld\n", decoded.text);
+
+        let line = search("wor", &decoded.text)[0];
+        // BOM 2 字节 + "héllo\n" 6 个码元
+        assert_eq!(14, decoded.source_offset(line_offset(&decoded.text, line)));
+    }
+
+    #[test]
+    fn latin1_and_forced_encoding() {
+        let bytes = b"caf\xe9 au lait";
+
+        assert!(encoding::decode(bytes, None).is_err());
+
+        let decoded = encoding::decode(bytes, Some(Encoding::Latin1)).unwrap();
+        assert_eq!("café au lait", decoded.text);
+        assert_eq!(5, decoded.source_offset(decoded.text.find("au").unwrap()));
+    }
+
+    #[test]
+    fn json_field() {
+        let field = Field::Json(String::from("level"));
+        let contents = r#"{"level":"error","msg":"disk full"}
+{"level":"info","msg":"error count reset"}
+not json at all, error
+{"msg":"no level","ctx":{"level":"error"}}"#;
+
+        assert_eq!(
+            vec![r#"{"level":"error","msg":"disk full"}"#],
+            fields::search("error", contents, &field)
+        );
+
+        let nested = Field::Json(String::from("ctx.level"));
+        assert_eq!(
+            vec![r#"{"msg":"no level","ctx":{"level":"error"}}"#],
+            fields::search_case_insensitive("ERROR", contents, &nested)
+        );
+    }
+
+    #[test]
+    fn completions_is_also_a_query() {
+        let parse = |args: &[&str]| {
+            let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+            Command::parse(&args)
+        };
+
+        assert!(matches!(
+            parse(&["minigrep", "completions", "zsh"]),
+            Ok(Command::Completions(Shell::Zsh))
+        ));
+        match parse(&["minigrep", "completions", "poem.txt"]) {
+            Ok(Command::Search(config)) => {
+                assert_eq!("completions", config.query);
+                assert_eq!("poem.txt", config.filename);
+            }
+            _ => panic!("expected a search"),
+        }
+        assert!(parse(&["minigrep", "completions"]).is_err());
+        // 既不是shell也不是已有文件时报告不支持的shell
+        assert_eq!(
+            Some(String::from(
+                "unknown shell: tcsh (expected bash, zsh, fish)"
+            )),
+            parse(&["minigrep", "completions", "tcsh"]).err()
+        );
+        assert_eq!(
+            Some(String::from("unexpected argument for completions: -b")),
+            parse(&["minigrep", "completions", "bash", "-b"]).err()
+        );
+    }
+
+    #[test]
+    fn json_field_rejects_invalid_json() {
+        let field = Field::Json(String::from("v"));
+        assert_eq!(
+            Some(String::from("😀")),
+            field.extract(r#"{"v":"\ud83d\ude00"}"#)
+        );
+        // 高位代理后面不是低位代理
+        assert_eq!(None, field.extract(r#"{"v":"\ud800\uffff"}"#));
+        assert_eq!(None, field.extract(r#"{"v":"\ud800\u0041"}"#));
+        // 只接受 JSON 定义的转义，控制字符必须转义
+        for escape in [r"\x41", r"\'", r"\a", r"\U0041", "\n"] {
+            let record = format!(r#"{{"v":"a{}"}}"#, escape);
+            assert_eq!(None, field.extract(&record), "{}", escape);
+        }
+        assert_eq!(
+            Some(String::from("a/\"\\")),
+            field.extract(r#"{"v":"a\/\"\\"}"#)
+        );
+        // 对象和数组中的字符串按 JSON 的规则转义，而不是 Rust 的 {:?}
+        assert_eq!(
+            Some(String::from(r#"{"k\"":["it's","\u0001\n\\"]}"#)),
+            field.extract(r#"{"v":{"k\"":["it's","\u0001\n\\"]}}"#)
+        );
+
+        for number in ["0", "-12", "3.25", "1e9", "-0.5E-3"] {
+            let record = format!(r#"{{"v":{}}}"#, number);
+            assert_eq!(Some(String::from(number)), field.extract(&record));
+        }
+        for number in ["inf", "NaN", "+1", ".5", "01", "1.", "1e", "infinity"] {
+            let record = format!(r#"{{"v":{}}}"#, number);
+            assert_eq!(None, field.extract(&record), "{}", number);
+        }
+    }
+
+    #[test]
+    fn binary_hex_query() {
+        let needle = binary::parse_query("\\x7fELF").unwrap();
+        assert_eq!(vec![0x7f, b'E', b'L', b'F'], needle);
+        assert!(binary::parse_query("\\x7").is_err());
+        assert!(binary::parse_query("\\x+f").is_err());
+        assert!(binary::parse_query("\\x-1").is_err());
+        assert_eq!(vec![0xab], binary::parse_query("\\xAb").unwrap());
+
+        let contents = b"\x00\x7fELF\x02\x01\xff\x7felf";
+        assert_eq!(vec![1], binary::search(&needle, contents));
+        assert_eq!(
+            vec![1, 8],
+            binary::search_case_insensitive(&needle, contents)
+        );
+
+        assert_eq!(
+            "00000000  00 7f 45 4c 46 02 01 ff  7f 65 6c 66              |..ELF....elf|\n",
+            binary::hex_dump(contents, 1, needle.len())
+        );
+    }
+
+    #[test]
+    fn every_option_is_documented() {
+        let man = cli::man_page();
+        for spec in cli::OPTIONS {
+            let long = format!("--{}", spec.long);
+            for shell in Shell::ALL {
+                let script = cli::completions(shell);
+                assert!(script.contains(spec.long), "{} {:?}", long, shell);
+            }
+            assert!(man.contains(&long.replace('-', "\\-")), "{}", long);
+            assert_eq!(spec.opt, cli::lookup(&long).unwrap().opt);
+        }
+    }
+
+    #[test]
+    fn csv_column_with_quotes() {
+        let field = Field::Column {
+            index: 3,
+            delimiter: ',',
+        };
+        let contents = "\
+1,\"Smith, John\",admin
+2,Doe,\"says \"\"admin\"\"\"
+3,admin,user";
+
+        assert_eq!(
+            vec!["1,\"Smith, John\",admin", "2,Doe,\"says \"\"admin\"\"\""],
+            fields::search("admin", contents, &field)
+        );
+        assert_eq!(
+            vec!["1", "Smith, John", "admin"],
+            fields::split_record("1,\"Smith, John\",admin", ',')
+        );
+    }
 }
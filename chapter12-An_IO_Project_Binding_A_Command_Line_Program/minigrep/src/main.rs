@@ -1,6 +1,7 @@
+use minigrep::cli;
+use minigrep::Command;
 use std::env;
 use std::process;
-use minigrep::Config;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
@@ -8,11 +9,24 @@ fn main() {
     // 当前执行的二进制文件名称
     // let file: &String = &args[0];
 
-    let config = Config::new(&args).unwrap_or_else(|err| {
+    let command = Command::parse(&args).unwrap_or_else(|err| {
         eprintln!("Problem parsing arguments: {}", err);
         process::exit(1)
     });
 
+    let config = match command {
+        Command::Search(config) => config,
+        // 补全脚本和man手册都由cli::OPTIONS生成，输出后直接退出
+        Command::Completions(shell) => {
+            print!("{}", cli::completions(shell));
+            return;
+        }
+        Command::Man => {
+            print!("{}", cli::man_page());
+            return;
+        }
+    };
+
     // println!("Seachering for {}", config.query);
     // println!("In file {}", config.filename);
 
@@ -24,4 +38,3 @@ fn main() {
         process::exit(1);
     };
 }
-
@@ -0,0 +1,291 @@
+// 端到端测试：运行编译好的minigrep二进制文件，
+// 把退出码、stdout和stderr与tests/snapshots下的快照文件比较。
+// 行为有意变更时，设置 MINIGREP_UPDATE_SNAPSHOTS=1 重新运行测试即可重新生成快照。
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn tests_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests")
+}
+
+// 在fixtures目录下运行minigrep，这样快照里出现的都是相对路径
+fn run(args: &[&str], case_insensitive: bool) -> String {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_minigrep"));
+    command.args(args).current_dir(tests_dir().join("fixtures"));
+
+    // 设置了CASE_SENSITIVE环境变量时minigrep不区分大小写
+    if case_insensitive {
+        command.env("CASE_SENSITIVE", "1");
+    } else {
+        command.env_remove("CASE_SENSITIVE");
+    }
+
+    let output = command.output().expect("failed to run minigrep");
+
+    format!(
+        "$ {}minigrep {}\nstatus: {}\n--- stdout\n{}--- stderr\n{}",
+        if case_insensitive {
+            "CASE_SENSITIVE=1 "
+        } else {
+            ""
+        },
+        args.join(" "),
+        output.status.code().unwrap_or(-1),
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr),
+    )
+}
+
+fn check(name: &str, args: &[&str]) {
+    assert_snapshot(name, &run(args, false));
+}
+
+fn check_case_insensitive(name: &str, args: &[&str]) {
+    assert_snapshot(name, &run(args, true));
+}
+
+fn assert_snapshot(name: &str, actual: &str) {
+    let path = tests_dir().join("snapshots").join(format!("{}.snap", name));
+
+    if env::var("MINIGREP_UPDATE_SNAPSHOTS").is_ok() {
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path).unwrap_or_else(|_| {
+        panic!(
+            "missing snapshot {}; run with MINIGREP_UPDATE_SNAPSHOTS=1 to create it\n{}",
+            path.display(),
+            actual
+        )
+    });
+    assert_eq!(expected, actual, "snapshot {} does not match", name);
+}
+
+#[test]
+fn plain_search() {
+    check("plain_search", &["body", "poem.txt"]);
+    check("no_match", &["monomorphization", "poem.txt"]);
+}
+
+#[test]
+fn case_insensitive_search() {
+    check_case_insensitive("case_insensitive", &["tO", "poem.txt"]);
+}
+
+#[test]
+fn byte_offsets() {
+    check("byte_offset_short", &["-b", "nobody", "poem.txt"]);
+    check("byte_offset_long", &["--byte-offset", "frog", "poem.txt"]);
+}
+
+#[test]
+fn argument_errors() {
+    check("no_arguments", &[]);
+    check("missing_filename", &["nobody"]);
+    check("missing_file", &["nobody", "does_not_exist.txt"]);
+    check(
+        "missing_option_value",
+        &["nobody", "poem.txt", "--encoding"],
+    );
+    check(
+        "unknown_encoding",
+        &["--encoding", "ebcdic", "x", "poem.txt"],
+    );
+}
+
+#[test]
+fn encodings() {
+    check("utf16le_bom", &["-b", "Trust", "utf16le.txt"]);
+    check("utf16be_bom", &["-b", "Köln", "utf16be.txt"]);
+    check("latin1_rejected_as_utf8", &["café", "latin1.txt"]);
+    check(
+        "latin1_forced",
+        &["--encoding", "latin1", "-b", "café", "latin1.txt"],
+    );
+    check(
+        "utf16le_forced_inline",
+        &["--encoding=UTF-16LE", "fast", "utf16le.txt"],
+    );
+    check_case_insensitive("utf16le_case_insensitive", &["TRUST", "utf16le.txt"]);
+}
+
+#[test]
+fn json_fields() {
+    check("json_field", &["--field", "level", "error", "logs.jsonl"]);
+    check(
+        "json_nested_field",
+        &["--field=http.status", "50", "logs.jsonl"],
+    );
+    check_case_insensitive(
+        "json_field_case_insensitive",
+        &["--field", "level", "error", "logs.jsonl"],
+    );
+    check(
+        "json_field_byte_offset",
+        &["-b", "--field", "msg", "error", "logs.jsonl"],
+    );
+}
+
+#[test]
+fn csv_columns() {
+    check("csv_column", &["--column", "3", "admin", "users.csv"]);
+    check_case_insensitive(
+        "csv_column_case_insensitive",
+        &["--column=3", "admin", "users.csv"],
+    );
+    check(
+        "tsv_column_by_extension",
+        &["--column", "2", "admin", "users.tsv"],
+    );
+    check(
+        "csv_column_explicit_delimiter",
+        &["--delimiter", "tab", "--column", "3", "admin", "users.tsv"],
+    );
+    check("csv_column_zero", &["--column", "0", "admin", "users.csv"]);
+    check(
+        "csv_bad_delimiter",
+        &["--delimiter", "::", "--column", "1", "x", "users.csv"],
+    );
+    check(
+        "field_and_column",
+        &["--field", "a", "--column", "1", "x", "users.csv"],
+    );
+    check(
+        "delimiter_without_column",
+        &["--delimiter", "tab", "admin", "users.csv"],
+    );
+}
+
+#[test]
+fn binary_search() {
+    check("binary_hex_dump", &["-a", "\\x7fELF", "binary.bin"]);
+    check(
+        "binary_offsets",
+        &["--binary", "-b", "\\x7fELF", "binary.bin"],
+    );
+    check_case_insensitive(
+        "binary_case_insensitive",
+        &["-a", "-b", "needle", "binary.bin"],
+    );
+    check("binary_bad_escape", &["-a", "\\x7", "binary.bin"]);
+    check(
+        "binary_with_field",
+        &["-a", "--field", "level", "x", "logs.jsonl"],
+    );
+    check(
+        "binary_with_encoding",
+        &["-a", "--encoding", "utf-16le", "x", "utf16le.txt"],
+    );
+    check("binary_signed_escape", &["-a", "\\x+f", "binary.bin"]);
+}
+
+// 选项两两组合：能一起使用的组合检查输出，不能一起使用的组合检查错误信息
+#[test]
+fn flag_combinations() {
+    check_case_insensitive(
+        "byte_offset_case_insensitive",
+        &["-b", "NOBODY", "poem.txt"],
+    );
+    check_case_insensitive(
+        "encoding_case_insensitive",
+        &["--encoding", "latin1", "CAFÉ", "latin1.txt"],
+    );
+    check_case_insensitive(
+        "encoding_byte_offset_case_insensitive",
+        &["-b", "--encoding", "utf-16le", "TRUST", "utf16le.txt"],
+    );
+    check(
+        "encoding_and_field",
+        &[
+            "--encoding",
+            "utf-8",
+            "--field",
+            "level",
+            "error",
+            "logs.jsonl",
+        ],
+    );
+    check(
+        "encoding_and_column",
+        &[
+            "--encoding",
+            "latin1",
+            "--column",
+            "3",
+            "admin",
+            "users.csv",
+        ],
+    );
+    check(
+        "byte_offset_and_column",
+        &["-b", "--column", "3", "admin", "users.csv"],
+    );
+    check(
+        "byte_offset_column_delimiter",
+        &[
+            "-b",
+            "--delimiter",
+            "tab",
+            "--column",
+            "3",
+            "admin",
+            "users.tsv",
+        ],
+    );
+    check_case_insensitive(
+        "field_byte_offset_case_insensitive",
+        &["-b", "--field", "level", "ERROR", "logs.jsonl"],
+    );
+    check_case_insensitive(
+        "column_delimiter_case_insensitive",
+        &["--delimiter=tab", "--column=3", "ADMIN", "users.tsv"],
+    );
+
+    check(
+        "binary_with_column",
+        &["-a", "--column", "1", "x", "users.csv"],
+    );
+    check(
+        "binary_with_field_and_column",
+        &["-a", "--field", "a", "--column", "1", "x", "users.csv"],
+    );
+    check(
+        "field_with_delimiter",
+        &[
+            "--field",
+            "level",
+            "--delimiter",
+            ",",
+            "error",
+            "logs.jsonl",
+        ],
+    );
+    // 子命令后面还有其他参数时按普通搜索处理
+    check("completions_with_flag", &["completions", "bash", "-b"]);
+}
+
+#[test]
+fn option_parsing() {
+    check("unknown_option", &["--colour", "x", "poem.txt"]);
+    check("flag_with_value", &["--byte-offset=1", "x", "poem.txt"]);
+    check("dash_dash_query", &["-b", "--", "-", "poem.txt"]);
+}
+
+#[test]
+fn completions_and_man_page() {
+    check("completions_bash", &["completions", "bash"]);
+    check("completions_zsh", &["completions", "zsh"]);
+    check("completions_fish", &["completions", "fish"]);
+    check("completions_unknown_shell", &["completions", "tcsh"]);
+    check("completions_missing_shell", &["completions"]);
+    // 第二个参数不是shell时，completions 是要搜索的词
+    check("completions_as_query", &["completions", "poem.txt"]);
+    check("generate_man", &["--generate-man"]);
+    check(
+        "generate_man_with_search",
+        &["-b", "--generate-man", "x", "poem.txt"],
+    );
+}
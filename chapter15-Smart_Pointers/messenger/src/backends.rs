@@ -0,0 +1,307 @@
+// Messenger 的实际实现：写到标准输出或标准错误、追加到文件（超过大小后轮转）、
+// 通过 HTTP 或 Unix socket 发送给本地服务，以及同时发给多个 Messenger 的 FanOut。
+//
+// Messenger::send 没有返回值，所以发送失败时消息被丢弃；
+// 需要知道是否成功时直接调用各个类型的 deliver。
+
+use std::cell::RefCell;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::{Messenger, Severity};
+
+// 每条消息一行："[warning] Urgent warning!"。
+// 消息中的换行写成 \n 和 \r，反斜杠写成 \\，一条消息不会被读成多行
+fn line(severity: Severity, msg: &str) -> String {
+    let mut line = format!("[{}] ", severity.name());
+    for c in msg.chars() {
+        match c {
+            '\\' => line.push_str("\\\\"),
+            '\n' => line.push_str("\\n"),
+            '\r' => line.push_str("\\r"),
+            c => line.push(c),
+        }
+    }
+    line.push('\n');
+    line
+}
+
+// 写入任意 io::Write，如标准输出或内存中的缓冲区
+pub struct StreamMessenger<W: Write> {
+    // send 只接收 &self，和 MockMessenger 一样通过 RefCell 写入
+    out: RefCell<W>,
+}
+
+impl StreamMessenger<io::Stdout> {
+    pub fn stdout() -> StreamMessenger<io::Stdout> {
+        StreamMessenger::new(io::stdout())
+    }
+}
+
+impl StreamMessenger<io::Stderr> {
+    pub fn stderr() -> StreamMessenger<io::Stderr> {
+        StreamMessenger::new(io::stderr())
+    }
+}
+
+impl<W: Write> StreamMessenger<W> {
+    pub fn new(out: W) -> StreamMessenger<W> {
+        StreamMessenger {
+            out: RefCell::new(out),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out.into_inner()
+    }
+
+    pub fn deliver(&self, severity: Severity, msg: &str) -> io::Result<()> {
+        let mut out = self.out.borrow_mut();
+        out.write_all(line(severity, msg).as_bytes())?;
+        out.flush()
+    }
+}
+
+impl<W: Write> Messenger for StreamMessenger<W> {
+    fn send(&self, msg: &str) {
+        self.alert(Severity::Info, msg)
+    }
+
+    fn alert(&self, severity: Severity, msg: &str) {
+        let _ = self.deliver(severity, msg);
+    }
+}
+
+// 追加到文件。文件超过 max_bytes 时依次改名为 <path>.1、<path>.2 ……，
+// 最多保留 keep 个旧文件
+pub struct FileMessenger {
+    path: PathBuf,
+    max_bytes: u64,
+    keep: usize,
+}
+
+impl FileMessenger {
+    pub fn new<P: AsRef<Path>>(path: P, max_bytes: u64, keep: usize) -> FileMessenger {
+        FileMessenger {
+            path: path.as_ref().to_path_buf(),
+            max_bytes,
+            keep,
+        }
+    }
+
+    // 第n个旧文件，n从1开始，越大越旧
+    pub fn rotated(&self, n: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    }
+
+    pub fn deliver(&self, severity: Severity, msg: &str) -> io::Result<()> {
+        let line = line(severity, msg);
+        let size = match fs::metadata(&self.path) {
+            Ok(meta) => meta.len(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e),
+        };
+        // 空文件不轮转，单条超过上限的消息也会写入
+        if size > 0 && size + line.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(line.as_bytes())
+    }
+
+    fn rotate(&self) -> io::Result<()> {
+        if self.keep == 0 {
+            return fs::remove_file(&self.path);
+        }
+        for n in (1..self.keep).rev() {
+            match fs::rename(self.rotated(n), self.rotated(n + 1)) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        fs::rename(&self.path, self.rotated(1))
+    }
+}
+
+impl Messenger for FileMessenger {
+    fn send(&self, msg: &str) {
+        self.alert(Severity::Info, msg)
+    }
+
+    fn alert(&self, severity: Severity, msg: &str) {
+        let _ = self.deliver(severity, msg);
+    }
+}
+
+// 每条消息一个 HTTP POST 请求，正文为消息文本，严重程度放在 X-Severity 头中
+pub struct HttpMessenger {
+    // host:port，如 127.0.0.1:8080
+    addr: String,
+    path: String,
+    timeout: Duration,
+}
+
+impl HttpMessenger {
+    pub fn new(addr: &str, path: &str) -> HttpMessenger {
+        HttpMessenger {
+            addr: String::from(addr),
+            path: String::from(path),
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> HttpMessenger {
+        self.timeout = timeout;
+        self
+    }
+
+    // 依次连接 addr 解析出的地址，每个地址最多等待 timeout
+    fn connect(&self) -> io::Result<TcpStream> {
+        let mut last = None;
+        for addr in self.addr.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, self.timeout) {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last = Some(e),
+            }
+        }
+        Err(last.unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} did not resolve to any address", self.addr),
+            )
+        }))
+    }
+
+    // 服务返回的不是 2xx 时视为失败
+    pub fn deliver(&self, severity: Severity, msg: &str) -> io::Result<()> {
+        let mut stream = self.connect()?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+
+        write!(
+            stream,
+            "POST {} HTTP/1.1\r\n\
+             Host: {}\r\n\
+             Content-Type: text/plain; charset=utf-8\r\n\
+             Content-Length: {}\r\n\
+             X-Severity: {}\r\n\
+             Connection: close\r\n\
+             \r\n\
+             {}",
+            self.path,
+            self.addr,
+            msg.len(),
+            severity.name(),
+            msg
+        )?;
+        stream.flush()?;
+
+        let mut status = String::new();
+        BufReader::new(stream).read_line(&mut status)?;
+        match status.split_whitespace().nth(1) {
+            Some(code) if code.starts_with('2') => Ok(()),
+            _ => Err(io::Error::other(format!(
+                "unexpected response: {}",
+                status.trim_end()
+            ))),
+        }
+    }
+}
+
+impl Messenger for HttpMessenger {
+    fn send(&self, msg: &str) {
+        self.alert(Severity::Info, msg)
+    }
+
+    fn alert(&self, severity: Severity, msg: &str) {
+        let _ = self.deliver(severity, msg);
+    }
+}
+
+// 每条消息建立一次连接，写入一行后关闭
+#[cfg(unix)]
+pub struct UnixSocketMessenger {
+    path: PathBuf,
+    timeout: Duration,
+}
+
+#[cfg(unix)]
+impl UnixSocketMessenger {
+    pub fn new<P: AsRef<Path>>(path: P) -> UnixSocketMessenger {
+        UnixSocketMessenger {
+            path: path.as_ref().to_path_buf(),
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    // 对方不读取时，写入最多等待 timeout
+    pub fn timeout(mut self, timeout: Duration) -> UnixSocketMessenger {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn deliver(&self, severity: Severity, msg: &str) -> io::Result<()> {
+        let mut stream = std::os::unix::net::UnixStream::connect(&self.path)?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        stream.write_all(line(severity, msg).as_bytes())
+    }
+}
+
+#[cfg(unix)]
+impl Messenger for UnixSocketMessenger {
+    fn send(&self, msg: &str) {
+        self.alert(Severity::Info, msg)
+    }
+
+    fn alert(&self, severity: Severity, msg: &str) {
+        let _ = self.deliver(severity, msg);
+    }
+}
+
+// 把每条消息依次发给所有的 Messenger
+#[derive(Default)]
+pub struct FanOut<'a> {
+    targets: Vec<Box<dyn Messenger + 'a>>,
+}
+
+impl<'a> FanOut<'a> {
+    pub fn new() -> FanOut<'a> {
+        FanOut::default()
+    }
+
+    pub fn with<M: Messenger + 'a>(mut self, messenger: M) -> FanOut<'a> {
+        self.targets.push(Box::new(messenger));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.targets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+    }
+}
+
+impl Messenger for FanOut<'_> {
+    fn send(&self, msg: &str) {
+        for target in &self.targets {
+            target.send(msg)
+        }
+    }
+
+    fn alert(&self, severity: Severity, msg: &str) {
+        for target in &self.targets {
+            target.alert(severity, msg)
+        }
+    }
+}
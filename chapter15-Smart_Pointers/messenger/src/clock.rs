@@ -0,0 +1,46 @@
+use std::cell::Cell;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// RateLimiter 的时间来源，时间均为自 UNIX 纪元以来的秒数
+pub trait Clock {
+    fn now(&self) -> u64;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+    }
+}
+
+// 只有调用 set 或 advance 时才会改变的时钟，用于测试。
+// 和 MockMessenger 里的 RefCell 一样用 Cell 保存时间，共享的 &ManualClock 也能推进时间
+pub struct ManualClock {
+    now: Cell<u64>,
+}
+
+impl ManualClock {
+    pub fn new(now: u64) -> ManualClock {
+        ManualClock {
+            now: Cell::new(now),
+        }
+    }
+
+    pub fn set(&self, now: u64) {
+        self.now.set(now);
+    }
+
+    pub fn advance(&self, secs: u64) {
+        self.now.set(self.now.get().saturating_add(secs));
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> u64 {
+        self.now.get()
+    }
+}
@@ -1,36 +1,93 @@
+mod backends;
+mod clock;
+mod policy;
+mod quota;
+mod window;
+
+#[cfg(unix)]
+pub use backends::UnixSocketMessenger;
+pub use backends::{FanOut, FileMessenger, HttpMessenger, StreamMessenger};
+pub use clock::{Clock, ManualClock, SystemClock};
+pub use policy::{Alert, AlertState, Level, Severity, ThresholdPolicy};
+pub use quota::{QuotaError, QuotaTracker, Report};
+pub use window::{RateLimiter, Window, WindowCounter, DAY, HOUR, MINUTE};
+
 pub trait Messenger {
     fn send(&self, msg: &str);
+
+    // 带有严重程度的消息，默认忽略严重程度直接发送
+    fn alert(&self, severity: Severity, msg: &str) {
+        let _ = severity;
+        self.send(msg)
+    }
+}
+
+// 借用的 Messenger 也是 Messenger，如放入 FanOut 后仍要在别处使用的
+impl<M: Messenger + ?Sized> Messenger for &M {
+    fn send(&self, msg: &str) {
+        (**self).send(msg)
+    }
+
+    fn alert(&self, severity: Severity, msg: &str) {
+        (**self).alert(severity, msg)
+    }
 }
 
 pub struct LimitTracker<'a, T: 'a + Messenger> {
     messenger: &'a T,
     value: usize,
     max: usize,
+    policy: ThresholdPolicy,
+    state: AlertState,
 }
 
 impl<'a, T> LimitTracker<'a, T>
 where
     T: Messenger,
 {
-    pub fn new(messenger: &T, max: usize) -> LimitTracker<T> {
+    pub fn new(messenger: &'a T, max: usize) -> LimitTracker<'a, T> {
+        LimitTracker::with_policy(messenger, max, ThresholdPolicy::default())
+    }
+
+    pub fn with_policy(
+        messenger: &'a T,
+        max: usize,
+        policy: ThresholdPolicy,
+    ) -> LimitTracker<'a, T> {
         LimitTracker {
             messenger,
             value: 0,
             max,
+            policy,
+            state: AlertState::new(),
         }
     }
 
+    pub fn value(&self) -> usize {
+        self.value
+    }
+
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    pub fn policy(&self) -> &ThresholdPolicy {
+        &self.policy
+    }
+
+    // 已经发出且尚未解除的最高级别
+    pub fn active_level(&self) -> Option<&Level> {
+        self.state.active(&self.policy)
+    }
+
+    // 越过更高的级别时发送它的消息，全部解除时发送恢复消息
     pub fn set_value(&mut self, value: usize) {
         self.value = value;
 
         let percentage_of_max = self.value as f64 / self.max as f64;
 
-        if percentage_of_max >= 1.0 {
-            self.messenger.send("Error: You are over you quota!")
-        } else if percentage_of_max >= 0.9 {
-            self.messenger.send("Urgent warning!")
-        } else if percentage_of_max >= 0.75 {
-            self.messenger.send("Warning!")
+        if let Some(alert) = self.state.update(&self.policy, percentage_of_max) {
+            alert.send(self.messenger)
         }
     }
 }
@@ -74,4 +131,507 @@ mod tests {
         // assert_eq!(mock_messenger.sent_message.len(), 1);
         assert_eq!(mock_messenger.sent_message.borrow().len(), 1);
     }
+
+    #[test]
+    fn default_policy_keeps_old_messages() {
+        let mock_messenger = MockMessenger::new();
+        let mut limit_tracker = LimitTracker::new(&mock_messenger, 100);
+
+        // 默认没有回落幅度，低于75%后再次达到就会重新发送
+        for value in [50, 75, 89, 90, 100, 150, 74, 75] {
+            limit_tracker.set_value(value);
+        }
+
+        assert_eq!(
+            *mock_messenger.sent_message.borrow(),
+            vec![
+                "Warning!",
+                "Urgent warning!",
+                "Error: You are over you quota!",
+                "Warning!",
+            ]
+        );
+    }
+
+    // 记录严重程度的Messenger
+    struct SeverityMessenger {
+        alerts: RefCell<Vec<(Severity, String)>>,
+    }
+
+    impl Messenger for SeverityMessenger {
+        fn send(&self, message: &str) {
+            self.alert(Severity::Info, message)
+        }
+
+        fn alert(&self, severity: Severity, message: &str) {
+            self.alerts
+                .borrow_mut()
+                .push((severity, String::from(message)));
+        }
+    }
+
+    #[test]
+    fn custom_policy() {
+        let policy = ThresholdPolicy::new()
+            .level(0.8, Severity::Critical, "80% used")
+            .level(0.5, Severity::Info, "half used")
+            .level(0.8, Severity::Warning, "80% of quota used");
+        assert_eq!(
+            vec![0.5, 0.8],
+            policy
+                .levels()
+                .iter()
+                .map(|l| l.threshold)
+                .collect::<Vec<_>>()
+        );
+
+        let messenger = SeverityMessenger {
+            alerts: RefCell::new(vec![]),
+        };
+        let mut limit_tracker = LimitTracker::with_policy(&messenger, 10, policy);
+        limit_tracker.set_value(4);
+        limit_tracker.set_value(5);
+        limit_tracker.set_value(12);
+
+        assert_eq!(
+            *messenger.alerts.borrow(),
+            vec![
+                (Severity::Info, String::from("half used")),
+                (Severity::Warning, String::from("80% of quota used")),
+            ]
+        );
+    }
+
+    #[test]
+    fn repeated_values_alert_once() {
+        let mock_messenger = MockMessenger::new();
+        let mut limit_tracker = LimitTracker::new(&mock_messenger, 100);
+
+        for _ in 0..3 {
+            limit_tracker.set_value(80);
+        }
+        assert_eq!(*mock_messenger.sent_message.borrow(), vec!["Warning!"]);
+        assert_eq!(
+            Some(Severity::Warning),
+            limit_tracker.active_level().map(|l| l.severity)
+        );
+    }
+
+    #[test]
+    fn hysteresis_and_recovery() {
+        let policy = ThresholdPolicy::default()
+            .hysteresis(0.1)
+            .recovered("Back under quota.");
+        let mock_messenger = MockMessenger::new();
+        let mut limit_tracker = LimitTracker::with_policy(&mock_messenger, 100, policy);
+
+        limit_tracker.set_value(95);
+        // 在90%附近波动不会重复发送
+        limit_tracker.set_value(85);
+        limit_tracker.set_value(92);
+        // 低于80%后90%的级别解除，75%的级别仍然保持
+        limit_tracker.set_value(79);
+        assert_eq!(
+            Some(0.75),
+            limit_tracker.active_level().map(|l| l.threshold)
+        );
+        limit_tracker.set_value(91);
+        limit_tracker.set_value(70);
+        limit_tracker.set_value(64);
+        assert_eq!(None, limit_tracker.active_level());
+        limit_tracker.set_value(10);
+        limit_tracker.set_value(76);
+
+        assert_eq!(
+            *mock_messenger.sent_message.borrow(),
+            vec![
+                "Urgent warning!",
+                "Urgent warning!",
+                "Back under quota.",
+                "Warning!",
+            ]
+        );
+    }
+
+    #[test]
+    fn alert_state_outlives_its_policy() {
+        let mut state = AlertState::new();
+        let default = ThresholdPolicy::default();
+        assert_eq!(
+            Some(Severity::Error),
+            state.update(&default, 1.2).map(|alert| match alert {
+                Alert::Level(level) => level.severity,
+                Alert::Recovered(_) => Severity::Info,
+            })
+        );
+
+        // 换成级别更少的策略不会越界，保持的是不高于原级别的最高级别
+        let shorter = ThresholdPolicy::new().level(0.5, Severity::Warning, "Half way.");
+        assert_eq!(Some(0.5), state.active(&shorter).map(|l| l.threshold));
+        assert_eq!(None, state.update(&shorter, 0.8));
+        assert_eq!(None, state.update(&shorter, 0.1));
+        assert_eq!(None, state.active(&shorter));
+    }
+
+    #[test]
+    #[should_panic(expected = "threshold must be a finite, non-negative ratio")]
+    fn invalid_thresholds_are_rejected() {
+        ThresholdPolicy::new().level(f64::INFINITY, Severity::Error, "Never.");
+    }
+
+    #[test]
+    #[should_panic(expected = "hysteresis must be a finite, non-negative ratio")]
+    fn negative_hysteresis_is_rejected() {
+        ThresholdPolicy::default().hysteresis(-0.1);
+    }
+
+    #[test]
+    #[should_panic(expected = "hysteresis must be a finite, non-negative ratio")]
+    fn nan_hysteresis_is_rejected() {
+        ThresholdPolicy::default().hysteresis(f64::NAN);
+    }
+
+    #[test]
+    fn named_resources_have_their_own_limits() {
+        let mock_messenger = MockMessenger::new();
+        let mut quotas = QuotaTracker::new(&mock_messenger);
+        quotas.add_resource("api_calls", 1000, ThresholdPolicy::default());
+        quotas.add_resource(
+            "seats",
+            10,
+            ThresholdPolicy::new()
+                .level(1.0, Severity::Error, "No seats left.")
+                .recovered("Seats available again."),
+        );
+
+        assert_eq!(Ok(800), quotas.add_usage("api_calls", 800));
+        assert_eq!(Ok(850), quotas.add_usage("api_calls", 50));
+        assert_eq!(Ok(10), quotas.add_usage("seats", 10));
+        assert_eq!(Ok(9), quotas.add_usage("seats", -1));
+        assert_eq!(Ok(0), quotas.add_usage("seats", -20));
+        assert_eq!(
+            Err(QuotaError::UnknownResource(String::from("storage"))),
+            quotas.add_usage("storage", 1)
+        );
+
+        assert_eq!(
+            *mock_messenger.sent_message.borrow(),
+            vec![
+                "api_calls: Warning!",
+                "seats: No seats left.",
+                "seats: Seats available again.",
+            ]
+        );
+    }
+
+    #[test]
+    fn quota_reports() {
+        let messenger = SeverityMessenger {
+            alerts: RefCell::new(vec![]),
+        };
+        let mut quotas = QuotaTracker::new(&messenger);
+        quotas.add_resource("storage", 200, ThresholdPolicy::default());
+        quotas.add_resource("api_calls", 100, ThresholdPolicy::default());
+        quotas.set_usage("storage", 190).unwrap();
+        quotas.set_usage("api_calls", 10).unwrap();
+        messenger.alerts.borrow_mut().clear();
+
+        assert_eq!(
+            Some(Severity::Critical),
+            quotas.report("storage").unwrap().severity
+        );
+        quotas.send_reports();
+        assert_eq!(
+            *messenger.alerts.borrow(),
+            vec![
+                (Severity::Info, String::from("api_calls: 10/100 (10%) ok")),
+                (
+                    Severity::Critical,
+                    String::from("storage: 190/200 (95%) critical")
+                ),
+            ]
+        );
+
+        quotas.remove_resource("storage").unwrap();
+        assert_eq!(None, quotas.report("storage"));
+        assert!(quotas.remove_resource("storage").is_err());
+    }
+
+    #[test]
+    fn huge_usage_does_not_overflow_the_percentage() {
+        let report = Report {
+            name: String::from("bytes"),
+            usage: usize::MAX,
+            max: usize::MAX / 2,
+            severity: Some(Severity::Critical),
+        };
+        assert_eq!(
+            format!("bytes: {}/{} (200%) critical", usize::MAX, usize::MAX / 2),
+            report.to_string()
+        );
+
+        let empty = Report { max: 0, ..report };
+        assert_eq!(
+            format!("bytes: {}/0 critical", usize::MAX),
+            empty.to_string()
+        );
+    }
+
+    #[test]
+    fn fixed_window_resets_on_the_boundary() {
+        let mut counter = WindowCounter::fixed(MINUTE);
+        counter.add(60, 3);
+        counter.add(90, 4);
+        counter.add(119, 1);
+        assert_eq!(8, counter.count(119));
+        assert_eq!(0, counter.count(120));
+        counter.add(150, 2);
+        assert_eq!(2, counter.count(179));
+    }
+
+    #[test]
+    fn sliding_window_decays_gradually() {
+        let mut counter = WindowCounter::sliding(MINUTE);
+        counter.add(0, 3);
+        counter.add(30, 4);
+        counter.add(30, 1);
+        assert_eq!(8, counter.count(59));
+        assert_eq!(5, counter.count(60));
+        assert_eq!(5, counter.count(89));
+        assert_eq!(0, counter.count(90));
+    }
+
+    #[test]
+    fn windows_ignore_a_clock_that_goes_backwards() {
+        let mut counter = WindowCounter::sliding(MINUTE);
+        counter.add(100, 3);
+        // 时钟回拨到上一个窗口，按之前见过的时间计算
+        counter.add(20, 2);
+        assert_eq!(5, counter.count(30));
+        assert_eq!(5, counter.count(159));
+        assert_eq!(0, counter.count(160));
+
+        let mut counter = WindowCounter::fixed(MINUTE);
+        counter.add(119, 1);
+        assert_eq!(1, counter.count(60));
+        counter.add(u64::MAX, usize::MAX);
+        counter.add(u64::MAX, 1);
+        assert_eq!(usize::MAX, counter.count(u64::MAX));
+    }
+
+    #[test]
+    fn try_record_does_not_overflow() {
+        let clock = ManualClock::new(0);
+        let mock_messenger = MockMessenger::new();
+        let mut limiter = RateLimiter::new(
+            &mock_messenger,
+            &clock,
+            WindowCounter::fixed(MINUTE),
+            10,
+            ThresholdPolicy::default(),
+        );
+
+        assert_eq!(5, limiter.record(5));
+        assert!(!limiter.try_record(usize::MAX));
+        assert_eq!(5, limiter.refresh());
+    }
+
+    #[test]
+    fn rate_limiter_alerts_follow_the_window() {
+        let clock = ManualClock::new(1_000);
+        let mock_messenger = MockMessenger::new();
+        let mut limiter = RateLimiter::new(
+            &mock_messenger,
+            &clock,
+            WindowCounter::sliding(HOUR),
+            10,
+            ThresholdPolicy::default().recovered("Rate back to normal."),
+        );
+
+        assert_eq!(5, limiter.record(5));
+        clock.advance(HOUR / 2);
+        assert_eq!(8, limiter.record(3));
+        assert!(limiter.try_record(2));
+        assert!(!limiter.try_record(1));
+        assert_eq!(10, limiter.tracker().value());
+
+        // 第一次的5个移出窗口
+        clock.advance(HOUR / 2);
+        assert_eq!(5, limiter.refresh());
+        assert!(limiter.try_record(1));
+        clock.advance(HOUR);
+        assert_eq!(0, limiter.refresh());
+
+        assert_eq!(
+            *mock_messenger.sent_message.borrow(),
+            vec![
+                "Warning!",
+                "Error: You are over you quota!",
+                "Rate back to normal.",
+            ]
+        );
+    }
+
+    // 测试用的临时目录，每个测试一个
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("messenger-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn stream_messenger_writes_lines() {
+        let messenger = StreamMessenger::new(Vec::new());
+        let mut limit_tracker = LimitTracker::new(&messenger, 100);
+        limit_tracker.set_value(95);
+        messenger.send("done");
+        // 消息中的换行不会拆成多行
+        messenger.alert(Severity::Error, "first\r\nsecond\\n");
+
+        assert_eq!(
+            "[critical] Urgent warning!\n[info] done\n[error] first\\r\\nsecond\\\\n\n",
+            String::from_utf8(messenger.into_inner()).unwrap()
+        );
+    }
+
+    #[test]
+    fn file_messenger_rotates() {
+        let dir = temp_dir("file");
+        let path = dir.join("alerts.log");
+        // 每行 "[info] message N\n" 为17字节，一个文件放两行
+        let messenger = FileMessenger::new(&path, 40, 2);
+        for n in 1..=7 {
+            messenger
+                .deliver(Severity::Info, &format!("message {}", n))
+                .unwrap();
+        }
+
+        let read = |path: &std::path::Path| std::fs::read_to_string(path).unwrap();
+        assert_eq!("[info] message 7\n", read(&path));
+        assert_eq!(
+            "[info] message 5\n[info] message 6\n",
+            read(&messenger.rotated(1))
+        );
+        assert_eq!(
+            "[info] message 3\n[info] message 4\n",
+            read(&messenger.rotated(2))
+        );
+        assert!(!messenger.rotated(3).exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn http_messenger_posts_to_a_local_server() {
+        use std::io::{BufRead, BufReader, Read, Write};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+
+        // 代替真实 webhook 的本地服务：收两个请求，第二个返回500
+        let server = std::thread::spawn(move || {
+            let mut received = Vec::new();
+            for status in ["204 No Content", "500 Internal Server Error"] {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut head = Vec::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    head.push(line.trim_end().to_string());
+                }
+                let length: usize = head
+                    .iter()
+                    .find_map(|h| h.strip_prefix("Content-Length: "))
+                    .unwrap()
+                    .parse()
+                    .unwrap();
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n",
+                    status
+                )
+                .unwrap();
+                received.push((head, String::from_utf8(body).unwrap()));
+            }
+            received
+        });
+
+        let messenger = HttpMessenger::new(&addr, "/hooks/quota");
+        messenger.deliver(Severity::Warning, "Warning!").unwrap();
+        assert!(messenger.deliver(Severity::Info, "again").is_err());
+        assert!(HttpMessenger::new("not an address", "/")
+            .deliver(Severity::Info, "lost")
+            .is_err());
+
+        let received = server.join().unwrap();
+        let (head, body) = &received[0];
+        assert_eq!("POST /hooks/quota HTTP/1.1", head[0]);
+        assert!(head.contains(&String::from("X-Severity: warning")));
+        assert_eq!("Warning!", body);
+        assert_eq!("again", received[1].1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_socket_messenger() {
+        use std::io::Read;
+        use std::os::unix::net::UnixListener;
+
+        let dir = temp_dir("socket");
+        let path = dir.join("alerts.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let messenger = UnixSocketMessenger::new(&path);
+        messenger.alert(Severity::Error, "over quota");
+        let mut text = String::new();
+        listener
+            .accept()
+            .unwrap()
+            .0
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!("[error] over quota\n", text);
+
+        // 对方一直不读取时，写满缓冲区后在超时后失败
+        let messenger = messenger.timeout(std::time::Duration::from_millis(50));
+        let _pending = std::os::unix::net::UnixStream::connect(&path);
+        assert!(messenger
+            .deliver(Severity::Info, &"x".repeat(16 << 20))
+            .is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn fan_out_broadcasts() {
+        let mock_messenger = MockMessenger::new();
+        let severities = SeverityMessenger {
+            alerts: RefCell::new(vec![]),
+        };
+        let fan_out = FanOut::new().with(&mock_messenger).with(&severities);
+        assert_eq!(2, fan_out.len());
+
+        let mut limit_tracker = LimitTracker::new(&fan_out, 100);
+        limit_tracker.set_value(100);
+
+        assert_eq!(
+            *mock_messenger.sent_message.borrow(),
+            vec!["Error: You are over you quota!"]
+        );
+        assert_eq!(
+            *severities.alerts.borrow(),
+            vec![(
+                Severity::Error,
+                String::from("Error: You are over you quota!")
+            )]
+        );
+    }
 }
@@ -0,0 +1,164 @@
+// 阈值策略：用量达到上限的某个比例时发送对应的消息。
+// 默认策略与原来写死的行为一致：75% 警告、90% 紧急警告、100% 超出配额。
+//
+// 每个级别只在用量向上越过它时发送一次，用量回落到 threshold - hysteresis
+// 以下后才会再次发送；全部级别解除时可以发送一条恢复消息。
+
+use crate::Messenger;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+    Error,
+}
+
+impl Severity {
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+            Severity::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Level {
+    // 占上限的比例，1.0 表示达到上限
+    pub threshold: f64,
+    pub severity: Severity,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThresholdPolicy {
+    // 按 threshold 从低到高排列
+    levels: Vec<Level>,
+    // 级别解除所需的回落幅度，与 threshold 一样是占上限的比例
+    hysteresis: f64,
+    // 所有级别都解除时发送的消息
+    recovered: Option<String>,
+}
+
+impl ThresholdPolicy {
+    // 没有任何级别的策略，从不发送消息
+    pub fn new() -> ThresholdPolicy {
+        ThresholdPolicy {
+            levels: Vec::new(),
+            hysteresis: 0.0,
+            recovered: None,
+        }
+    }
+
+    // 添加一个级别，threshold 相同时替换原来的级别
+    pub fn level(mut self, threshold: f64, severity: Severity, message: &str) -> ThresholdPolicy {
+        assert!(
+            threshold.is_finite() && threshold >= 0.0,
+            "threshold must be a finite, non-negative ratio"
+        );
+        self.levels.retain(|l| l.threshold != threshold);
+        let at = self.levels.partition_point(|l| l.threshold < threshold);
+        self.levels.insert(
+            at,
+            Level {
+                threshold,
+                severity,
+                message: String::from(message),
+            },
+        );
+        self
+    }
+
+    pub fn hysteresis(mut self, margin: f64) -> ThresholdPolicy {
+        assert!(
+            margin.is_finite() && margin >= 0.0,
+            "hysteresis must be a finite, non-negative ratio"
+        );
+        self.hysteresis = margin;
+        self
+    }
+
+    pub fn recovered(mut self, message: &str) -> ThresholdPolicy {
+        self.recovered = Some(String::from(message));
+        self
+    }
+
+    pub fn levels(&self) -> &[Level] {
+        &self.levels
+    }
+
+    // ratio 达到的最高级别
+    pub fn check(&self, ratio: f64) -> Option<&Level> {
+        self.levels.iter().rev().find(|l| ratio >= l.threshold)
+    }
+}
+
+impl Default for ThresholdPolicy {
+    fn default() -> ThresholdPolicy {
+        ThresholdPolicy::new()
+            .level(0.75, Severity::Warning, "Warning!")
+            .level(0.9, Severity::Critical, "Urgent warning!")
+            .level(1.0, Severity::Error, "Error: You are over you quota!")
+    }
+}
+
+// 要发送的提醒
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Alert<'a> {
+    Level(&'a Level),
+    Recovered(&'a str),
+}
+
+impl Alert<'_> {
+    pub fn send<M: Messenger + ?Sized>(&self, messenger: &M) {
+        match self {
+            Alert::Level(level) => messenger.alert(level.severity, &level.message),
+            Alert::Recovered(message) => messenger.alert(Severity::Info, message),
+        }
+    }
+}
+
+// 记录已经发出的最高级别，避免用量不变或小幅波动时重复发送。
+// 保存的是级别的 threshold 而不是下标，换用另一个策略时也不会越界
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AlertState {
+    // 已发出且尚未解除的最高级别的 threshold
+    active: Option<f64>,
+}
+
+impl AlertState {
+    pub fn new() -> AlertState {
+        AlertState::default()
+    }
+
+    pub fn active<'p>(&self, policy: &'p ThresholdPolicy) -> Option<&'p Level> {
+        self.active.and_then(|threshold| policy.check(threshold))
+    }
+
+    // 用量变为 ratio 后需要发送的提醒
+    pub fn update<'p>(&mut self, policy: &'p ThresholdPolicy, ratio: f64) -> Option<Alert<'p>> {
+        let reached = policy.check(ratio);
+        // None 小于任何 Some，只有越过更高的级别时才发送
+        if reached.map(|l| l.threshold) > self.active {
+            self.active = reached.map(|l| l.threshold);
+            return reached.map(Alert::Level);
+        }
+
+        let active = self.active?;
+        // 回落不到 hysteresis 的级别仍然保持
+        self.active = policy
+            .levels
+            .iter()
+            .rev()
+            .filter(|l| l.threshold <= active)
+            .find(|l| ratio >= l.threshold - policy.hysteresis)
+            .map(|l| l.threshold);
+        match self.active {
+            None => policy.recovered.as_deref().map(Alert::Recovered),
+            Some(_) => None,
+        }
+    }
+}
@@ -0,0 +1,167 @@
+// 多个资源的配额：每个资源有自己的名称、上限和阈值策略，
+// 提醒消息以资源名开头，通过同一个 Messenger 发送。
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::{AlertState, Messenger, Severity, ThresholdPolicy};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuotaError {
+    UnknownResource(String),
+}
+
+impl fmt::Display for QuotaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QuotaError::UnknownResource(name) => write!(f, "unknown resource {:?}", name),
+        }
+    }
+}
+
+impl Error for QuotaError {}
+
+struct Resource {
+    usage: usize,
+    max: usize,
+    policy: ThresholdPolicy,
+    state: AlertState,
+}
+
+// 一个资源当前的用量
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub name: String,
+    pub usage: usize,
+    pub max: usize,
+    // 已经发出且尚未解除的最高级别
+    pub severity: Option<Severity>,
+}
+
+impl fmt::Display for Report {
+    // api_calls: 80/100 (80%) warning
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}/{}", self.name, self.usage, self.max)?;
+        // 用u128计算，接近usize::MAX的用量乘以100也不会溢出
+        if let Some(percent) = (self.usage as u128 * 100).checked_div(self.max as u128) {
+            write!(f, " ({}%)", percent)?;
+        }
+        match self.severity {
+            Some(severity) => write!(f, " {}", severity.name()),
+            None => write!(f, " ok"),
+        }
+    }
+}
+
+pub struct QuotaTracker<'a, T: 'a + Messenger> {
+    messenger: &'a T,
+    // 按名称排列，报告的顺序固定
+    resources: BTreeMap<String, Resource>,
+}
+
+impl<'a, T> QuotaTracker<'a, T>
+where
+    T: Messenger,
+{
+    pub fn new(messenger: &'a T) -> QuotaTracker<'a, T> {
+        QuotaTracker {
+            messenger,
+            resources: BTreeMap::new(),
+        }
+    }
+
+    // 添加一个用量为0的资源，同名的资源会被替换
+    pub fn add_resource(&mut self, name: &str, max: usize, policy: ThresholdPolicy) {
+        self.resources.insert(
+            String::from(name),
+            Resource {
+                usage: 0,
+                max,
+                policy,
+                state: AlertState::new(),
+            },
+        );
+    }
+
+    pub fn remove_resource(&mut self, name: &str) -> Result<(), QuotaError> {
+        self.resources
+            .remove(name)
+            .map(|_| ())
+            .ok_or_else(|| QuotaError::UnknownResource(String::from(name)))
+    }
+
+    pub fn usage(&self, name: &str) -> Option<usize> {
+        self.resources.get(name).map(|r| r.usage)
+    }
+
+    pub fn set_usage(&mut self, name: &str, usage: usize) -> Result<(), QuotaError> {
+        let resource = self
+            .resources
+            .get_mut(name)
+            .ok_or_else(|| QuotaError::UnknownResource(String::from(name)))?;
+        resource.usage = usage;
+
+        let ratio = resource.usage as f64 / resource.max as f64;
+        if let Some(alert) = resource.state.update(&resource.policy, ratio) {
+            alert.send(&Prefixed {
+                name,
+                messenger: self.messenger,
+            })
+        }
+        Ok(())
+    }
+
+    // 增加或减少用量（如释放席位），不会小于0，返回新的用量
+    pub fn add_usage(&mut self, name: &str, delta: isize) -> Result<usize, QuotaError> {
+        let usage = self
+            .usage(name)
+            .ok_or_else(|| QuotaError::UnknownResource(String::from(name)))?
+            .saturating_add_signed(delta);
+        self.set_usage(name, usage)?;
+        Ok(usage)
+    }
+
+    pub fn report(&self, name: &str) -> Option<Report> {
+        self.resources.get(name).map(|r| Report {
+            name: String::from(name),
+            usage: r.usage,
+            max: r.max,
+            severity: r.state.active(&r.policy).map(|l| l.severity),
+        })
+    }
+
+    pub fn reports(&self) -> Vec<Report> {
+        self.resources
+            .keys()
+            .filter_map(|name| self.report(name))
+            .collect()
+    }
+
+    // 每个资源发送一条报告，严重程度为该资源当前的级别
+    pub fn send_reports(&self) {
+        for report in self.reports() {
+            self.messenger.alert(
+                report.severity.unwrap_or(Severity::Info),
+                &report.to_string(),
+            )
+        }
+    }
+}
+
+// 在消息前加上资源名
+struct Prefixed<'a, T: Messenger> {
+    name: &'a str,
+    messenger: &'a T,
+}
+
+impl<T: Messenger> Messenger for Prefixed<'_, T> {
+    fn send(&self, msg: &str) {
+        self.messenger.send(&format!("{}: {}", self.name, msg))
+    }
+
+    fn alert(&self, severity: Severity, msg: &str) {
+        self.messenger
+            .alert(severity, &format!("{}: {}", self.name, msg))
+    }
+}
@@ -0,0 +1,151 @@
+// 按时间窗口统计的用量：固定窗口在每个整分钟、整点或整天清零，
+// 滑动窗口只统计最近一段时间内的用量，旧的用量随时间逐渐移出。
+// RateLimiter 用窗口内的用量驱动 LimitTracker，提醒反映的是当前窗口。
+
+use std::collections::VecDeque;
+
+use crate::{Clock, LimitTracker, Messenger, ThresholdPolicy};
+
+pub const MINUTE: u64 = 60;
+pub const HOUR: u64 = 60 * MINUTE;
+pub const DAY: u64 = 24 * HOUR;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Window {
+    // 窗口从 length 的整数倍开始，如每个整点
+    Fixed,
+    // 窗口为 (now - length, now]
+    Sliding,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowCounter {
+    kind: Window,
+    // 窗口长度，秒
+    length: u64,
+    // (时间, 用量)，按时间排列，同一秒的用量合并为一项
+    entries: VecDeque<(u64, usize)>,
+    // 见过的最晚时间，时钟回拨时按这个时间计算
+    latest: u64,
+}
+
+impl WindowCounter {
+    pub fn fixed(length: u64) -> WindowCounter {
+        WindowCounter::new(Window::Fixed, length)
+    }
+
+    pub fn sliding(length: u64) -> WindowCounter {
+        WindowCounter::new(Window::Sliding, length)
+    }
+
+    fn new(kind: Window, length: u64) -> WindowCounter {
+        assert!(length > 0, "window length must be positive");
+        WindowCounter {
+            kind,
+            length,
+            entries: VecDeque::new(),
+            latest: 0,
+        }
+    }
+
+    pub fn kind(&self) -> Window {
+        self.kind
+    }
+
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+
+    // 早于之前见过的时间的 now 按之前的时间计算，entries 始终按时间排列
+    pub fn add(&mut self, now: u64, amount: usize) {
+        let now = self.evict(now);
+        match self.entries.back_mut() {
+            Some((at, total)) if *at == now => *total = total.saturating_add(amount),
+            _ => self.entries.push_back((now, amount)),
+        }
+    }
+
+    // now 所在窗口内的用量
+    pub fn count(&mut self, now: u64) -> usize {
+        self.evict(now);
+        self.entries
+            .iter()
+            .fold(0, |sum, (_, amount)| sum.saturating_add(*amount))
+    }
+
+    // 窗口内最早的时间
+    fn start(&self, now: u64) -> u64 {
+        match self.kind {
+            Window::Fixed => now - now % self.length,
+            Window::Sliding => now.saturating_sub(self.length - 1),
+        }
+    }
+
+    // 移出窗口外的用量，返回调整后的 now
+    fn evict(&mut self, now: u64) -> u64 {
+        let now = now.max(self.latest);
+        self.latest = now;
+        let start = self.start(now);
+        while self.entries.front().is_some_and(|(at, _)| *at < start) {
+            self.entries.pop_front();
+        }
+        now
+    }
+}
+
+pub struct RateLimiter<'a, T: 'a + Messenger> {
+    tracker: LimitTracker<'a, T>,
+    counter: WindowCounter,
+    clock: &'a dyn Clock,
+}
+
+impl<'a, T> RateLimiter<'a, T>
+where
+    T: Messenger,
+{
+    // 每个窗口最多 max 的用量，达到策略中的级别时通过 messenger 提醒
+    pub fn new(
+        messenger: &'a T,
+        clock: &'a dyn Clock,
+        counter: WindowCounter,
+        max: usize,
+        policy: ThresholdPolicy,
+    ) -> RateLimiter<'a, T> {
+        RateLimiter {
+            tracker: LimitTracker::with_policy(messenger, max, policy),
+            counter,
+            clock,
+        }
+    }
+
+    pub fn tracker(&self) -> &LimitTracker<'a, T> {
+        &self.tracker
+    }
+
+    // 记录用量，返回当前窗口内的用量
+    pub fn record(&mut self, amount: usize) -> usize {
+        self.counter.add(self.clock.now(), amount);
+        self.refresh()
+    }
+
+    // 只有不超过上限时才记录，返回是否记录
+    pub fn try_record(&mut self, amount: usize) -> bool {
+        match self.refresh().checked_add(amount) {
+            Some(total) if total <= self.tracker.max() => {
+                self.record(amount);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // 移出窗口外的用量并重新检查提醒，返回当前窗口内的用量。
+    // 没有新用量时定期调用，才能在用量回落后发送恢复消息
+    pub fn refresh(&mut self) -> usize {
+        let usage = self.counter.count(self.clock.now());
+        if usage != self.tracker.value() {
+            self.tracker.set_value(usage);
+        }
+        usage
+    }
+}
@@ -0,0 +1,618 @@
+// blog 命令行工具：在本地的文章仓库上驱动 Post 的状态转换
+
+use std::error::Error;
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::clock::{Clock, SystemClock};
+use crate::site::{self, SiteConfig, Templates};
+use crate::store;
+use crate::{
+    Comment, CommentId, Comments, FileRepository, LineRange, Post, PostId, PostRepository,
+    PostState, Reviewer, StoreError, Version,
+};
+
+pub const USAGE: &str = "\
+usage: blog [--store DIR] <command> [args]
+
+commands:
+  new [--author NAME] [--title T] [--tag TAG]... [TEXT]
+                                      create a draft and print its id
+  edit <ID> <TEXT> [--author NAME]    append TEXT to a draft
+  submit <ID>                         request review of a draft
+  approve <ID> --as NAME [--role R]   approve a post pending review
+  reject <ID> <REASON> [--as NAME]    send a post pending review back to draft
+  comment <ID> <TEXT> --as NAME [--lines N[-M]]
+                                      comment on a post pending review
+  reply <ID> <COMMENT> <TEXT> --as NAME
+                                      reply to a comment
+  resolve <ID> <COMMENT> [--as NAME]  resolve a comment thread (author only)
+  schedule <ID> <TIME>                publish once approved and TIME (unix seconds) is reached
+  archive <ID>                        take a published post offline
+  tick                                publish scheduled posts that are due
+  show <ID>                           print a post
+  list [--state S] [--tag T] [--author A]
+                                      list posts, optionally filtered
+  search <QUERY>                      list published posts containing every word of QUERY
+  build <OUT_DIR> [--templates DIR] [--title T] [--base-url URL]
+                                      render published posts, an index, tag pages and an
+                                      Atom feed; templates default to <store>/templates
+
+--store may be given with any command; other options only with the commands listed above.
+arguments after -- are never treated as options, so TEXT may start with --";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    New {
+        author: String,
+        title: Option<String>,
+        tags: Vec<String>,
+        text: Option<String>,
+    },
+    Edit {
+        id: PostId,
+        author: Option<String>,
+        text: String,
+    },
+    Submit {
+        id: PostId,
+    },
+    Approve {
+        id: PostId,
+        reviewer: Reviewer,
+    },
+    Reject {
+        id: PostId,
+        reviewer: Option<String>,
+        reason: String,
+    },
+    Comment {
+        id: PostId,
+        author: String,
+        text: String,
+        lines: Option<LineRange>,
+    },
+    Reply {
+        id: PostId,
+        parent: CommentId,
+        author: String,
+        text: String,
+    },
+    Resolve {
+        id: PostId,
+        comment: CommentId,
+        by: Option<String>,
+    },
+    Schedule {
+        id: PostId,
+        publish_at: u64,
+    },
+    Archive {
+        id: PostId,
+    },
+    Tick,
+    Show {
+        id: PostId,
+    },
+    List {
+        state: Option<PostState>,
+        tag: Option<String>,
+        author: Option<String>,
+    },
+    Search {
+        query: String,
+    },
+    Build {
+        out_dir: PathBuf,
+        templates: PathBuf,
+        title: String,
+        base_url: String,
+    },
+}
+
+pub struct Config {
+    // 文章仓库所在的目录，默认为当前目录下的 .blog
+    pub store: PathBuf,
+    pub command: Command,
+}
+
+impl Config {
+    pub fn new(args: &[String]) -> Result<Config, String> {
+        let mut store = PathBuf::from(".blog");
+        let mut author = None;
+        let mut reviewer = None;
+        let mut roles = Vec::new();
+        let mut state = None;
+        let mut title = None;
+        let mut tags = Vec::new();
+        let mut tag = None;
+        let mut lines = None;
+        let mut templates = None;
+        let mut base_url = None;
+        let mut positional = Vec::new();
+        // 用到的选项（--store 除外），确定命令后检查是否适用
+        let mut options: Vec<String> = Vec::new();
+
+        // 跳过args[0]，即当前执行的二进制文件名称
+        let mut iter = args.iter().skip(1);
+        while let Some(arg) = iter.next() {
+            // -- 之后的参数都不是选项，文章内容可以以 -- 开头
+            if arg == "--" {
+                positional.extend(iter.by_ref().cloned());
+                break;
+            }
+
+            let mut value = || {
+                iter.next()
+                    .cloned()
+                    .ok_or_else(|| format!("{} requires a value", arg))
+            };
+
+            if arg.starts_with("--") && arg != "--store" {
+                options.push(arg.clone());
+            }
+            match arg.as_str() {
+                "--store" => store = PathBuf::from(value()?),
+                "--author" => author = Some(value()?),
+                "--as" => reviewer = Some(value()?),
+                "--role" => roles.push(value()?),
+                "--state" => state = Some(value()?),
+                "--title" => title = Some(value()?),
+                "--lines" => lines = Some(parse_lines(&value()?)?),
+                "--templates" => templates = Some(PathBuf::from(value()?)),
+                "--base-url" => base_url = Some(value()?),
+                // new 可以指定多个标签，list 只按最后一个筛选
+                "--tag" => {
+                    let value = value()?;
+                    tag = Some(value.clone());
+                    tags.push(value);
+                }
+                _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+                _ => positional.push(arg.clone()),
+            }
+        }
+
+        let mut positional = positional.into_iter();
+        let name = positional.next().ok_or("missing command")?;
+        let rest: Vec<String> = positional.collect();
+
+        let command = match (name.as_str(), rest.as_slice()) {
+            ("new", []) => Command::New {
+                author: author.unwrap_or_default(),
+                title,
+                tags,
+                text: None,
+            },
+            ("new", [text]) => Command::New {
+                author: author.unwrap_or_default(),
+                title,
+                tags,
+                text: Some(text.clone()),
+            },
+            ("edit", [id, text]) => Command::Edit {
+                id: parse_id(id)?,
+                author,
+                text: text.clone(),
+            },
+            ("submit", [id]) => Command::Submit { id: parse_id(id)? },
+            ("approve", [id]) => {
+                let name = reviewer.ok_or("approve requires --as NAME")?;
+                let mut reviewer = Reviewer::new(&name);
+                for role in &roles {
+                    reviewer = reviewer.with_role(role);
+                }
+                Command::Approve {
+                    id: parse_id(id)?,
+                    reviewer,
+                }
+            }
+            ("reject", [id, reason]) => Command::Reject {
+                id: parse_id(id)?,
+                reviewer,
+                reason: reason.clone(),
+            },
+            ("comment", [id, text]) => Command::Comment {
+                id: parse_id(id)?,
+                author: reviewer.ok_or("comment requires --as NAME")?,
+                text: text.clone(),
+                lines,
+            },
+            ("reply", [id, parent, text]) => Command::Reply {
+                id: parse_id(id)?,
+                parent: parse_comment_id(parent)?,
+                author: reviewer.ok_or("reply requires --as NAME")?,
+                text: text.clone(),
+            },
+            ("resolve", [id, comment]) => Command::Resolve {
+                id: parse_id(id)?,
+                comment: parse_comment_id(comment)?,
+                by: reviewer,
+            },
+            ("schedule", [id, time]) => Command::Schedule {
+                id: parse_id(id)?,
+                publish_at: time
+                    .parse()
+                    .map_err(|_| format!("invalid time {}, expected unix seconds", time))?,
+            },
+            ("archive", [id]) => Command::Archive { id: parse_id(id)? },
+            ("tick", []) => Command::Tick,
+            ("show", [id]) => Command::Show { id: parse_id(id)? },
+            ("list", []) => Command::List {
+                state: match state {
+                    None => None,
+                    Some(name) => Some(PostState::from_name(&name).ok_or_else(|| {
+                        let names: Vec<&str> = PostState::all().iter().map(|s| s.name()).collect();
+                        format!(
+                            "unknown state {}, expected one of {}",
+                            name,
+                            names.join(", ")
+                        )
+                    })?),
+                },
+                tag,
+                author,
+            },
+            ("search", [query]) => Command::Search {
+                query: query.clone(),
+            },
+            ("build", [out_dir]) => Command::Build {
+                out_dir: PathBuf::from(out_dir),
+                templates: templates.unwrap_or_else(|| store.join("templates")),
+                title: title.unwrap_or_else(|| String::from("Blog")),
+                // 订阅源中的链接必须是绝对地址
+                base_url: match base_url {
+                    Some(url) if url.ends_with('/') => url,
+                    Some(url) => format!("{}/", url),
+                    None => String::from("http://localhost/"),
+                },
+            },
+            (
+                "new" | "edit" | "submit" | "approve" | "reject" | "comment" | "reply" | "resolve"
+                | "schedule" | "archive" | "tick" | "show" | "list" | "search" | "build",
+                _,
+            ) => return Err(format!("wrong number of arguments for {}", name)),
+            _ => return Err(format!("unknown command {}", name)),
+        };
+
+        // 不适用的选项会被忽略，所以直接报错
+        let allowed = allowed_options(&name);
+        if let Some(option) = options.iter().find(|o| !allowed.contains(&o.as_str())) {
+            return Err(format!("{} does not accept {}", name, option));
+        }
+
+        Ok(Config { store, command })
+    }
+}
+
+// 每个命令可以使用的选项，--store 对所有命令都有效
+fn allowed_options(command: &str) -> &'static [&'static str] {
+    match command {
+        "new" => &["--author", "--title", "--tag"],
+        "edit" => &["--author"],
+        "approve" => &["--as", "--role"],
+        "reject" | "reply" | "resolve" => &["--as"],
+        "comment" => &["--as", "--lines"],
+        "list" => &["--state", "--tag", "--author"],
+        "build" => &["--templates", "--title", "--base-url"],
+        _ => &[],
+    }
+}
+
+fn parse_id(id: &str) -> Result<PostId, String> {
+    id.parse().map_err(|_| format!("invalid post id {}", id))
+}
+
+fn parse_comment_id(id: &str) -> Result<CommentId, String> {
+    id.parse().map_err(|_| format!("invalid comment id {}", id))
+}
+
+// "3" 或 "3-5"
+fn parse_lines(text: &str) -> Result<LineRange, String> {
+    let invalid = || format!("invalid line range {}, expected N or N-M", text);
+    let (start, end) = text.split_once('-').unwrap_or((text, text));
+    Ok(LineRange::new(
+        start.parse().map_err(|_| invalid())?,
+        end.parse().map_err(|_| invalid())?,
+    ))
+}
+
+// 读出文章和版本号，之后的修改使用clock记录时间
+fn checkout<R: PostRepository + ?Sized, C: Clock + 'static>(
+    repo: &R,
+    id: PostId,
+    clock: &Arc<C>,
+) -> Result<(Post, Version), StoreError> {
+    let (mut post, version) = repo.get_versioned(id)?;
+    post.set_clock(Arc::clone(clock) as Arc<dyn Clock>);
+    Ok((post, version))
+}
+
+pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
+    let mut repo = FileRepository::open(&config.store)?;
+    let clock = Arc::new(SystemClock);
+    print!("{}", execute(&mut repo, &config.command, &clock)?);
+    Ok(())
+}
+
+// 执行一条命令并返回要输出的文本。
+// 非法的状态转换会返回错误，仓库中的文章保持不变。
+// 修改文章的命令只在读出文章后没有其他人写入时才保存，否则返回冲突错误。
+// 文章的所有时间戳都取自clock。
+pub fn execute<R: PostRepository + ?Sized, C: Clock + 'static>(
+    repo: &mut R,
+    command: &Command,
+    clock: &Arc<C>,
+) -> Result<String, Box<dyn Error>> {
+    match command {
+        Command::New {
+            author,
+            title,
+            tags,
+            text,
+        } => {
+            let mut post = Post {
+                author: author.to_string(),
+                ..Post::with_clock(Arc::clone(clock) as Arc<dyn Clock>)
+            };
+            if let Some(title) = title {
+                // 仓库在插入时保证slug唯一
+                post.set_title(title);
+            }
+            for tag in tags {
+                post.add_tag(tag);
+            }
+            if let Some(text) = text {
+                post.add_text(text);
+            }
+            let id = repo.insert(&post)?;
+            Ok(format!("{}\n", id))
+        }
+        Command::Edit { id, author, text } => {
+            let (mut post, version) = checkout(repo, *id, clock)?;
+            require_state(&post, *id, "draft", "edit")?;
+            let author = author.clone().unwrap_or_else(|| post.author().to_string());
+            post.edit(&author, text);
+            repo.update_if(*id, version, &post)?;
+            Ok(String::new())
+        }
+        Command::Submit { id } => {
+            let (mut post, version) = checkout(repo, *id, clock)?;
+            post.try_request_review()
+                .map_err(|e| format!("post {}: {}", id, e))?;
+            repo.update_if(*id, version, &post)?;
+            Ok(format!("post {} is {}\n", id, post.state_name()))
+        }
+        Command::Approve { id, reviewer } => {
+            let (mut post, version) = checkout(repo, *id, clock)?;
+            post.approve_by(reviewer)
+                .map_err(|e| format!("cannot approve post {}: {}", id, e))?;
+            repo.update_if(*id, version, &post)?;
+            Ok(format!("post {} is {}\n", id, post.state_name()))
+        }
+        Command::Reject {
+            id,
+            reviewer,
+            reason,
+        } => {
+            let (mut post, version) = checkout(repo, *id, clock)?;
+            let reviewer = Reviewer::new(reviewer.as_deref().unwrap_or(""));
+            post.try_reject_by(&reviewer, reason)
+                .map_err(|e| format!("post {}: {}", id, e))?;
+            repo.update_if(*id, version, &post)?;
+            Ok(format!("post {} is {}\n", id, post.state_name()))
+        }
+        Command::Comment {
+            id,
+            author,
+            text,
+            lines,
+        } => {
+            let (mut post, version) = checkout(repo, *id, clock)?;
+            let comment = post
+                .comment(author, text, *lines)
+                .map_err(|e| format!("cannot comment on post {}: {}", id, e))?;
+            repo.update_if(*id, version, &post)?;
+            Ok(format!("comment {}\n", comment))
+        }
+        Command::Reply {
+            id,
+            parent,
+            author,
+            text,
+        } => {
+            let (mut post, version) = checkout(repo, *id, clock)?;
+            let comment = post
+                .reply(*parent, author, text)
+                .map_err(|e| format!("cannot reply on post {}: {}", id, e))?;
+            repo.update_if(*id, version, &post)?;
+            Ok(format!("comment {}\n", comment))
+        }
+        Command::Resolve { id, comment, by } => {
+            let (mut post, version) = checkout(repo, *id, clock)?;
+            let by = by.clone().unwrap_or_else(|| post.author().to_string());
+            post.resolve_comment(*comment, &by)
+                .map_err(|e| format!("cannot resolve comment on post {}: {}", id, e))?;
+            repo.update_if(*id, version, &post)?;
+            Ok(String::new())
+        }
+        Command::Schedule { id, publish_at } => {
+            let (mut post, version) = checkout(repo, *id, clock)?;
+            post.try_schedule(*publish_at)
+                .map_err(|e| format!("post {}: {}", id, e))?;
+            repo.update_if(*id, version, &post)?;
+            Ok(format!("post {} is {}\n", id, post.state_name()))
+        }
+        Command::Archive { id } => {
+            let (mut post, version) = checkout(repo, *id, clock)?;
+            post.try_archive()
+                .map_err(|e| format!("post {}: {}", id, e))?;
+            repo.update_if(*id, version, &post)?;
+            Ok(format!("post {} is {}\n", id, post.state_name()))
+        }
+        Command::Tick => {
+            let mut out = String::new();
+            for id in store::publish_due(repo, clock.as_ref())? {
+                let _ = writeln!(out, "post {} is published", id);
+            }
+            Ok(out)
+        }
+        Command::Show { id } => Ok(show(*id, &repo.get(*id)?)),
+        Command::List { state, tag, author } => {
+            let mut out = String::new();
+            for id in repo.ids()? {
+                let post = repo.get(id)?;
+                if state.is_some_and(|s| s != post.state())
+                    || tag.as_deref().is_some_and(|t| !post.metadata().has_tag(t))
+                    || author.as_deref().is_some_and(|a| a != post.author())
+                {
+                    continue;
+                }
+                list_line(&mut out, id, &post);
+            }
+            Ok(out)
+        }
+        Command::Build {
+            out_dir,
+            templates,
+            title,
+            base_url,
+        } => {
+            let mut posts = Vec::new();
+            for id in repo.ids()? {
+                posts.push((id, repo.get(id)?));
+            }
+            let templates = Templates::load(templates)?;
+            let config = SiteConfig {
+                title: title.clone(),
+                base_url: base_url.clone(),
+            };
+            let pages = site::generate(&posts, &templates, &config);
+            let written = site::write(out_dir, &pages)?;
+            Ok(format!(
+                "wrote {} files to {}\n",
+                written,
+                out_dir.display()
+            ))
+        }
+        Command::Search { query } => {
+            let mut out = String::new();
+            for id in repo.ids()? {
+                let post = repo.get(id)?;
+                if post.matches(query) {
+                    list_line(&mut out, id, &post);
+                }
+            }
+            Ok(out)
+        }
+    }
+}
+
+// list和search的一行：id、状态、作者以及标题（没有标题时为第一行内容）
+fn list_line(out: &mut String, id: PostId, post: &Post) {
+    let title = if post.title().is_empty() {
+        text(post).lines().next().unwrap_or("")
+    } else {
+        post.title()
+    };
+    let _ = writeln!(
+        out,
+        "{}\t{}\t{}\t{}",
+        id,
+        post.state_name(),
+        post.author(),
+        title
+    );
+}
+
+fn require_state(post: &Post, id: PostId, state: &str, action: &str) -> Result<(), String> {
+    if post.state_name() == state {
+        Ok(())
+    } else {
+        Err(format!(
+            "cannot {} post {}: it is {}, not {}",
+            action,
+            id,
+            post.state_name(),
+            state
+        ))
+    }
+}
+
+// 已发布的文章显示content，其余状态显示最新修订版本的内容
+fn text(post: &Post) -> &str {
+    if post.state_name() == "published" {
+        post.content()
+    } else {
+        post.history().latest().map_or("", |r| r.content.as_str())
+    }
+}
+
+fn show(id: PostId, post: &Post) -> String {
+    let mut out = format!("post {} [{}]", id, post.state_name());
+    if !post.author().is_empty() {
+        let _ = write!(out, " by {}", post.author());
+    }
+    out.push('\n');
+
+    if !post.title().is_empty() {
+        let _ = writeln!(out, "title: {}", post.title());
+    }
+    if !post.slug().is_empty() {
+        let _ = writeln!(out, "slug: {}", post.slug());
+    }
+    if !post.tags().is_empty() {
+        let _ = writeln!(out, "tags: {}", post.tags().join(", "));
+    }
+    if let Some(publish_at) = post.publish_at() {
+        let _ = writeln!(out, "publish at: {}", publish_at);
+    }
+    if !post.approvals().is_empty() {
+        let names: Vec<&str> = post.approvals().iter().map(|r| r.name.as_str()).collect();
+        let _ = writeln!(out, "approved by: {}", names.join(", "));
+    }
+    for reason in post.rejections() {
+        let _ = writeln!(out, "rejected: {}", reason);
+    }
+    if !post.comments().is_empty() {
+        out.push_str("comments:\n");
+        for thread in post.comments().threads() {
+            show_comment(&mut out, post.comments(), thread, 1);
+        }
+    }
+
+    out.push_str("---\n");
+    out.push_str(text(post));
+    if !out.ends_with('\n') {
+        out.push('\n');
+    }
+    out
+}
+
+// 评论及其回复，回复比所回复的评论多缩进一级
+fn show_comment(out: &mut String, comments: &Comments, comment: &Comment, depth: usize) {
+    let _ = write!(
+        out,
+        "{}#{} {}",
+        "  ".repeat(depth),
+        comment.id,
+        comment.author
+    );
+    match comment.lines {
+        Some(lines) if lines.start == lines.end => {
+            let _ = write!(out, " (line {})", lines);
+        }
+        Some(lines) => {
+            let _ = write!(out, " (lines {})", lines);
+        }
+        None => {}
+    }
+    if comment.resolved {
+        out.push_str(" [resolved]");
+    }
+    let _ = writeln!(out, ": {}", comment.text);
+
+    for reply in comments.replies(comment.id) {
+        show_comment(out, comments, reply, depth + 1);
+    }
+}
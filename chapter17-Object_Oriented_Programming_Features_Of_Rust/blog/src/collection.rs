@@ -0,0 +1,73 @@
+// 一组以slug区分的文章，支持按标签、作者、状态筛选以及全文搜索
+
+use crate::metadata;
+use crate::Post;
+
+#[derive(Clone, Default)]
+pub struct Collection {
+    posts: Vec<Post>,
+}
+
+impl Collection {
+    pub fn new() -> Collection {
+        Collection::default()
+    }
+
+    // 加入一篇文章并返回它的slug。
+    // 标题生成的slug已被占用时自动追加序号，保证slug唯一。
+    pub fn insert(&mut self, mut post: Post) -> String {
+        let slug = metadata::unique_slug(post.title(), |slug| self.get(slug).is_some());
+        post.meta.slug = slug.clone();
+        self.posts.push(post);
+        slug
+    }
+
+    pub fn get(&self, slug: &str) -> Option<&Post> {
+        self.posts.iter().find(|p| p.slug() == slug)
+    }
+
+    pub fn get_mut(&mut self, slug: &str) -> Option<&mut Post> {
+        self.posts.iter_mut().find(|p| p.slug() == slug)
+    }
+
+    pub fn remove(&mut self, slug: &str) -> Option<Post> {
+        let index = self.posts.iter().position(|p| p.slug() == slug)?;
+        Some(self.posts.remove(index))
+    }
+
+    pub fn len(&self) -> usize {
+        self.posts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.posts.is_empty()
+    }
+
+    // 按加入的顺序遍历
+    pub fn iter(&self) -> impl Iterator<Item = &Post> {
+        self.posts.iter()
+    }
+
+    // 标签比较时不区分大小写
+    pub fn by_tag(&self, tag: &str) -> Vec<&Post> {
+        self.filter(|p| p.metadata().has_tag(tag))
+    }
+
+    pub fn by_author(&self, author: &str) -> Vec<&Post> {
+        self.filter(|p| p.author() == author)
+    }
+
+    // state为状态名称，如 "published"
+    pub fn by_state(&self, state: &str) -> Vec<&Post> {
+        self.filter(|p| p.state_name() == state)
+    }
+
+    // 在已发布文章的标题和内容中搜索，见Post::matches
+    pub fn search(&self, query: &str) -> Vec<&Post> {
+        self.filter(|p| p.matches(query))
+    }
+
+    fn filter<F: Fn(&Post) -> bool>(&self, predicate: F) -> Vec<&Post> {
+        self.posts.iter().filter(|p| predicate(p)).collect()
+    }
+}
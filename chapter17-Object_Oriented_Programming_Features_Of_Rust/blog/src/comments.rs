@@ -0,0 +1,190 @@
+// 审阅评论：审阅者在文章等待审阅时留下评论，可以针对某几行内容，
+// 其他人可以回复形成讨论串，作者处理后将整个讨论串标记为已解决。
+
+use std::error::Error;
+use std::fmt;
+
+pub type CommentId = usize;
+
+// 内容中的行范围，从1开始，包含两端
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl LineRange {
+    pub fn new(start: usize, end: usize) -> LineRange {
+        LineRange { start, end }
+    }
+
+    // 只有一行的范围
+    pub fn line(line: usize) -> LineRange {
+        LineRange::new(line, line)
+    }
+}
+
+impl fmt::Display for LineRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.start == self.end {
+            write!(f, "{}", self.start)
+        } else {
+            write!(f, "{}-{}", self.start, self.end)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    pub id: CommentId,
+    pub author: String,
+    pub text: String,
+    pub timestamp: u64,
+    // 回复的评论，讨论串的第一条评论为None
+    pub parent: Option<CommentId>,
+    // 只有讨论串的第一条评论可以指定行范围
+    pub lines: Option<LineRange>,
+    // 只对讨论串的第一条评论有意义
+    pub resolved: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommentError {
+    // 只能在文章等待审阅时评论
+    NotPendingReview,
+    NotFound(CommentId),
+    // 行范围超出了内容的行数，或者start大于end
+    InvalidLines { lines: LineRange, line_count: usize },
+    // 只有文章作者可以解决评论
+    NotAuthor(String),
+}
+
+impl fmt::Display for CommentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CommentError::NotPendingReview => write!(f, "post is not pending review"),
+            CommentError::NotFound(id) => write!(f, "comment {} does not exist", id),
+            CommentError::InvalidLines { lines, line_count } => write!(
+                f,
+                "lines {} are outside the post, which has {} lines",
+                lines, line_count
+            ),
+            CommentError::NotAuthor(name) => {
+                write!(f, "{} is not the author and cannot resolve comments", name)
+            }
+        }
+    }
+}
+
+impl Error for CommentError {}
+
+// 一篇文章的所有评论，按添加顺序保存，id从1开始
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Comments {
+    comments: Vec<Comment>,
+}
+
+impl Comments {
+    pub fn new() -> Comments {
+        Comments::default()
+    }
+
+    // 开始一个新的讨论串。line_count为当前内容的行数，用来检查lines
+    pub fn add(
+        &mut self,
+        author: &str,
+        text: &str,
+        lines: Option<LineRange>,
+        line_count: usize,
+        timestamp: u64,
+    ) -> Result<CommentId, CommentError> {
+        if let Some(lines) = lines {
+            if lines.start == 0 || lines.start > lines.end || lines.end > line_count {
+                return Err(CommentError::InvalidLines { lines, line_count });
+            }
+        }
+        Ok(self.push(author, text, None, lines, timestamp))
+    }
+
+    // 回复任意一条评论，回复属于同一个讨论串
+    pub fn reply(
+        &mut self,
+        parent: CommentId,
+        author: &str,
+        text: &str,
+        timestamp: u64,
+    ) -> Result<CommentId, CommentError> {
+        self.get(parent).ok_or(CommentError::NotFound(parent))?;
+        Ok(self.push(author, text, Some(parent), None, timestamp))
+    }
+
+    // 将id所在的讨论串标记为已解决
+    pub fn resolve(&mut self, id: CommentId) -> Result<(), CommentError> {
+        let root = self.root(id).ok_or(CommentError::NotFound(id))?;
+        self.comments[root - 1].resolved = true;
+        Ok(())
+    }
+
+    pub fn get(&self, id: CommentId) -> Option<&Comment> {
+        self.comments.get(id.checked_sub(1)?)
+    }
+
+    pub fn all(&self) -> &[Comment] {
+        &self.comments
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.comments.is_empty()
+    }
+
+    // 每个讨论串的第一条评论
+    pub fn threads(&self) -> impl Iterator<Item = &Comment> {
+        self.comments.iter().filter(|c| c.parent.is_none())
+    }
+
+    // id的直接回复
+    pub fn replies(&self, id: CommentId) -> impl Iterator<Item = &Comment> {
+        self.comments.iter().filter(move |c| c.parent == Some(id))
+    }
+
+    // 还没有解决的讨论串
+    pub fn unresolved(&self) -> impl Iterator<Item = &Comment> {
+        self.threads().filter(|c| !c.resolved)
+    }
+
+    // 沿着parent找到讨论串的第一条评论
+    fn root(&self, mut id: CommentId) -> Option<CommentId> {
+        loop {
+            match self.get(id)?.parent {
+                Some(parent) => id = parent,
+                None => return Some(id),
+            }
+        }
+    }
+
+    fn push(
+        &mut self,
+        author: &str,
+        text: &str,
+        parent: Option<CommentId>,
+        lines: Option<LineRange>,
+        timestamp: u64,
+    ) -> CommentId {
+        let id = self.comments.len() + 1;
+        self.comments.push(Comment {
+            id,
+            author: author.to_string(),
+            text: text.to_string(),
+            timestamp,
+            parent,
+            lines,
+            resolved: false,
+        });
+        id
+    }
+
+    // 从存储中还原时使用，评论按id顺序给出
+    pub(crate) fn restore(comments: Vec<Comment>) -> Comments {
+        Comments { comments }
+    }
+}
@@ -0,0 +1,68 @@
+// 状态转换的通知：Post 在状态改变后依次调用注册的 Listener。
+// 没有改变状态的操作（如未满足审批规则的批准、改期）不会产生事件。
+
+use std::fmt::Write;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransitionEvent {
+    pub from: &'static str,
+    pub to: &'static str,
+    // 执行操作的人；匿名操作和定时发布为空字符串
+    pub actor: String,
+    // 发生的时间，UNIX秒
+    pub at: u64,
+}
+
+// Post需要能在线程之间传递，所以Listener也必须是Send + Sync
+pub trait Listener: Send + Sync {
+    fn on_transition(&self, event: &TransitionEvent);
+}
+
+// 闭包也可以直接作为 Listener 注册
+impl<F: Fn(&TransitionEvent) + Send + Sync> Listener for F {
+    fn on_transition(&self, event: &TransitionEvent) {
+        self(event)
+    }
+}
+
+// 只能追加的审计日志，记录收到的每一个事件，供合规审查导出
+#[derive(Default)]
+pub struct AuditLog {
+    // 通过 Mutex 在 &self 中记录事件，多个线程中的文章可以共用一个日志
+    entries: Mutex<Vec<TransitionEvent>>,
+}
+
+impl AuditLog {
+    pub fn new() -> AuditLog {
+        AuditLog::default()
+    }
+
+    // 目前为止记录的所有事件，按发生顺序排列
+    pub fn entries(&self) -> Vec<TransitionEvent> {
+        self.entries.lock().unwrap().clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.lock().unwrap().is_empty()
+    }
+
+    // 每个事件一行："<时间>\t<from> -> <to>\t<actor>"
+    pub fn dump(&self) -> String {
+        let mut out = String::new();
+        for e in self.entries.lock().unwrap().iter() {
+            let _ = writeln!(out, "{}\t{} -> {}\t{}", e.at, e.from, e.to, e.actor);
+        }
+        out
+    }
+}
+
+impl Listener for AuditLog {
+    fn on_transition(&self, event: &TransitionEvent) {
+        self.entries.lock().unwrap().push(event.clone());
+    }
+}
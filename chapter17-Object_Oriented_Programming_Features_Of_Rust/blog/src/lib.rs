@@ -1,94 +1,2356 @@
+pub mod cli;
+pub mod clock;
+pub mod collection;
+pub mod comments;
+pub mod events;
+mod json;
+pub mod machine;
+pub mod markdown;
+pub mod metadata;
+pub mod review;
+pub mod revision;
+pub mod site;
+pub mod store;
+pub mod transition;
+
+pub use clock::{Clock, ManualClock, SystemClock};
+pub use collection::Collection;
+pub use comments::{Comment, CommentError, CommentId, Comments, LineRange};
+pub use events::{AuditLog, Listener, TransitionEvent};
+pub use metadata::Metadata;
+pub use review::{ApprovalError, ApprovalPolicy, Reviewer};
+pub use revision::{DiffLine, History, Revision};
+pub use store::{FileRepository, MemoryRepository, PostId, PostRepository, StoreError, Version};
+pub use transition::{Action, TransitionError};
+
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+
+use machine::StateMachine;
+
+#[derive(Clone)]
 pub struct Post {
-    state: Option<Box<dyn State>>,
+    state: PostState,
+    // 等待审阅时为目前已批准的审阅者，发布后为批准发布的审阅者；退回草稿时清空
+    approvals: Vec<Reviewer>,
+    // 安排的发布时间，只在PendingReview和Scheduled状态下有值
+    publish_at: Option<u64>,
     content: String,
+    // 每次被退回草稿时审阅者给出的理由，按时间顺序排列
+    rejections: Vec<String>,
+    author: String,
+    policy: ApprovalPolicy,
+    history: History,
+    meta: Metadata,
+    // 审阅评论，退回草稿后仍然保留，直到作者解决
+    comments: Comments,
+    // 状态改变时通知的对象，复制文章时共享同一组Listener
+    listeners: Vec<Arc<dyn Listener>>,
+    // 所有时间戳的来源，默认为系统时间，复制文章时共享同一个时钟
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for Post {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Post {
     pub fn new() -> Post {
+        Post::with_clock(Arc::new(SystemClock))
+    }
+
+    // 使用指定的时钟记录创建、修改、修订和状态改变的时间
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Post {
         Post {
-            state: Some(Box::new(Draft{})),
+            state: post_machine().initial(),
+            approvals: Vec::new(),
+            publish_at: None,
             content: String::new(),
+            rejections: Vec::new(),
+            author: String::new(),
+            policy: ApprovalPolicy::default(),
+            history: History::new(),
+            meta: Metadata::new(clock.now()),
+            comments: Comments::new(),
+            listeners: Vec::new(),
+            clock,
         }
     }
 
+    // 更换时钟，只影响之后记录的时间，例如从仓库读出的文章
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    // 指定作者，作者默认不能批准自己的文章
+    pub fn with_author(author: &str) -> Post {
+        Post {
+            author: author.to_string(),
+            ..Post::new()
+        }
+    }
+
+    pub fn author(&self) -> &str {
+        &self.author
+    }
+
+    pub fn metadata(&self) -> &Metadata {
+        &self.meta
+    }
+
+    pub fn title(&self) -> &str {
+        &self.meta.title
+    }
+
+    // 第一次设置标题时根据标题生成slug，之后slug保持不变
+    pub fn set_title(&mut self, title: &str) {
+        self.meta.title = title.to_string();
+        if self.meta.slug.is_empty() {
+            self.meta.slug = metadata::slugify(title);
+        }
+        self.touch();
+    }
+
+    pub fn slug(&self) -> &str {
+        &self.meta.slug
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.meta.tags
+    }
+
+    // 已有的标签（不区分大小写）不会重复添加
+    pub fn add_tag(&mut self, tag: &str) {
+        if !self.meta.has_tag(tag) {
+            self.meta.tags.push(tag.to_string());
+            self.touch();
+        }
+    }
+
+    pub fn remove_tag(&mut self, tag: &str) {
+        let before = self.meta.tags.len();
+        self.meta.tags.retain(|t| !t.eq_ignore_ascii_case(tag));
+        if self.meta.tags.len() != before {
+            self.touch();
+        }
+    }
+
+    pub fn created(&self) -> u64 {
+        self.meta.created
+    }
+
+    // 最后一次修改内容或元数据的时间
+    pub fn updated(&self) -> u64 {
+        self.meta.updated
+    }
+
+    // 最近一次发布的时间
+    pub fn published(&self) -> Option<u64> {
+        self.meta.published
+    }
+
+    fn touch(&mut self) {
+        self.meta.updated = self.clock.now();
+    }
+
+    // 已发布的文章的标题或内容是否包含query中的每一个词，不区分大小写。
+    // 未发布的文章和空白的query都不匹配。
+    pub fn matches(&self, query: &str) -> bool {
+        if self.state_name() != "published" {
+            return false;
+        }
+
+        let text = format!("{}\n{}", self.title(), self.content()).to_lowercase();
+        let mut terms = query.split_whitespace().peekable();
+        terms.peek().is_some() && terms.all(|term| text.contains(&term.to_lowercase()))
+    }
+
+    pub fn set_policy(&mut self, policy: ApprovalPolicy) {
+        self.policy = policy;
+    }
+
+    pub fn policy(&self) -> &ApprovalPolicy {
+        &self.policy
+    }
+
+    // 等待审阅时为目前已批准的审阅者，发布后为批准发布的审阅者
+    pub fn approvals(&self) -> &[Reviewer] {
+        &self.approvals
+    }
+
+    // 以文章作者的身份追加内容
     pub fn add_text(&mut self, test: &str) {
-        self.content.push_str(test);
+        let author = self.author.clone();
+        self.edit(&author, test);
     }
 
+    // 追加内容，并以author的身份记录一个新的修订版本
+    pub fn edit(&mut self, author: &str, text: &str) {
+        let now = self.clock.now();
+        self.content.push_str(text);
+        self.history.record(author, now, &self.content);
+        self.meta.updated = now;
+    }
+
+    // 修订记录与文章状态无关，审阅者在任何状态下都可以查看
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    pub fn revision(&self, number: usize) -> Option<&Revision> {
+        self.history.get(number)
+    }
+
+    pub fn diff(&self, from: usize, to: usize) -> Option<Vec<DiffLine>> {
+        self.history.diff(from, to)
+    }
+
+    // 只有已发布的文章才能读取内容，其余状态返回空字符串
     pub fn content(&self) -> &str {
-        // 调用as_ref时得到Option<&Box<dyn State>>
-        self.state.as_ref().unwrap().content(&self)
+        if self.state == PostState::Published {
+            &self.content
+        } else {
+            ""
+        }
+    }
+
+    // 把内容渲染为HTML，和content一样只有已发布的文章才有输出
+    pub fn render_html(&self) -> String {
+        markdown::to_html(self.content())
+    }
+
+    pub fn state(&self) -> PostState {
+        self.state
+    }
+
+    // 当前状态的名称，如 "draft"
+    pub fn state_name(&self) -> &'static str {
+        self.state.name()
+    }
+
+    pub fn rejections(&self) -> &[String] {
+        &self.rejections
     }
 
     // 请求审批文章的功能，将文章的状态从Draft变为PendingReview。
+    // 不允许的操作（如对已发布的文章请求审阅）会被忽略。
     pub fn request_review(&mut self) {
-        // request_review方法需要获取状态值的所有权
-        // 取出state字段的Some值
-        // 并在原来的位置留下一个None。
-        if let Some(s) = self.state.take() {
-            // 我们需要临时把state设置为None来取得state值的所有权，
-            // 而不能直接使用self.state = self.state.request_review();
-            // 确保Post无法在我们完成状态转换后再次使用旧的state值
-            self.state = Some(s.request_review())
-        }
+        let author = self.author.clone();
+        self.fire(Event::RequestReview, &author, self.clock.now());
     }
 
+    // 匿名批准，审批规则只要求一次批准时会直接发布文章。
+    // 不满足规则的批准会被忽略。
     pub fn approve(&mut self) {
-        if let Some(s) = self.state.take() {
-            self.state = Some(s.approve())
+        let _ = self.approve_by(&Reviewer::new(""));
+    }
+
+    // 以reviewer的身份批准文章，满足审批规则后才会转为Published
+    pub fn approve_by(&mut self, reviewer: &Reviewer) -> Result<(), ApprovalError> {
+        if !self.allows(Action::Approve) {
+            return Err(ApprovalError::NotPendingReview);
+        }
+        if !self.policy.allow_self_approval
+            && !self.author.is_empty()
+            && reviewer.name == self.author
+        {
+            return Err(ApprovalError::SelfApproval(reviewer.name.clone()));
+        }
+        if self.approvals.iter().any(|r| r.name == reviewer.name) {
+            return Err(ApprovalError::AlreadyApproved(reviewer.name.clone()));
+        }
+        let unresolved = self.comments.unresolved().count();
+        if unresolved > 0 {
+            return Err(ApprovalError::UnresolvedComments(unresolved));
+        }
+
+        // 先记下这次批准，再由守卫条件判断是否满足审批规则
+        self.approvals.push(reviewer.clone());
+        self.fire(Event::Approve, &reviewer.name, self.clock.now());
+        Ok(())
+    }
+
+    // 审阅者不同意发布时将文章从PendingReview退回Draft，并记录理由。
+    // 其他状态下调用不会产生任何效果，也不会记录理由。
+    pub fn reject(&mut self, reason: &str) {
+        self.reject_by(&Reviewer::new(""), reason);
+    }
+
+    // 与reject相同，但在事件中记录是哪位审阅者退回的
+    pub fn reject_by(&mut self, reviewer: &Reviewer, reason: &str) {
+        if self.allows(Action::Reject) {
+            self.rejections.push(reason.to_string());
+        }
+        self.fire(Event::Reject, &reviewer.name, self.clock.now());
+    }
+
+    pub fn comments(&self) -> &Comments {
+        &self.comments
+    }
+
+    // 审阅者在文章等待审阅时发表评论，lines为针对的最新修订版本中的行
+    pub fn comment(
+        &mut self,
+        author: &str,
+        text: &str,
+        lines: Option<LineRange>,
+    ) -> Result<CommentId, CommentError> {
+        if self.state != PostState::PendingReview {
+            return Err(CommentError::NotPendingReview);
+        }
+        let line_count = self.content.lines().count();
+        self.comments
+            .add(author, text, lines, line_count, self.clock.now())
+    }
+
+    // 回复一条评论，同样只能在等待审阅时进行
+    pub fn reply(
+        &mut self,
+        parent: CommentId,
+        author: &str,
+        text: &str,
+    ) -> Result<CommentId, CommentError> {
+        if self.state != PostState::PendingReview {
+            return Err(CommentError::NotPendingReview);
+        }
+        self.comments.reply(parent, author, text, self.clock.now())
+    }
+
+    // 作者将评论所在的讨论串标记为已解决，任何状态下都可以进行。
+    // 没有作者的文章任何人都可以解决评论。
+    pub fn resolve_comment(&mut self, id: CommentId, by: &str) -> Result<(), CommentError> {
+        if !self.author.is_empty() && by != self.author {
+            return Err(CommentError::NotAuthor(by.to_string()));
         }
+        self.comments.resolve(id)
+    }
+
+    // 注册一个Listener，之后每次状态改变都会通知它
+    pub fn subscribe(&mut self, listener: Arc<dyn Listener>) {
+        self.listeners.push(listener);
+    }
+
+    // 当前状态下是否可以执行action
+    pub fn allows(&self, action: Action) -> bool {
+        post_machine().accepts(self.state, Event::from(action))
+    }
+
+    // 下面的try_*方法与对应的方法行为相同，
+    // 但在当前状态下不允许该操作时返回错误，而不是静默忽略。
+    pub fn try_request_review(&mut self) -> Result<(), TransitionError> {
+        self.check(Action::RequestReview)?;
+        self.request_review();
+        Ok(())
+    }
+
+    // 匿名批准被拒绝，或者批准后仍不满足审批规则时也返回错误，
+    // 此时不会记录这次批准
+    pub fn try_approve(&mut self) -> Result<(), TransitionError> {
+        self.check(Action::Approve)?;
+        let reviewer = Reviewer::new("");
+        let mut approvals = self.approvals.clone();
+        approvals.push(reviewer.clone());
+        let result = if self.policy.is_satisfied(&approvals) {
+            self.approve_by(&reviewer)
+        } else {
+            Err(ApprovalError::PolicyNotMet)
+        };
+        result.map_err(|reason| TransitionError {
+            state: self.state_name(),
+            action: Action::Approve,
+            reason: Some(reason),
+        })
+    }
+
+    pub fn try_reject(&mut self, reason: &str) -> Result<(), TransitionError> {
+        self.try_reject_by(&Reviewer::new(""), reason)
+    }
+
+    pub fn try_reject_by(
+        &mut self,
+        reviewer: &Reviewer,
+        reason: &str,
+    ) -> Result<(), TransitionError> {
+        self.check(Action::Reject)?;
+        self.reject_by(reviewer, reason);
+        Ok(())
+    }
+
+    // 在审阅期间安排发布时间，满足审批规则后文章进入Scheduled状态，
+    // 而不是直接发布；已经进入Scheduled的文章可以改期
+    pub fn schedule(&mut self, publish_at: u64) {
+        if self.allows(Action::Schedule) {
+            self.publish_at = Some(publish_at);
+        }
+        self.fire(Event::Schedule, "", self.clock.now());
+    }
+
+    pub fn try_schedule(&mut self, publish_at: u64) -> Result<(), TransitionError> {
+        self.check(Action::Schedule)?;
+        self.schedule(publish_at);
+        Ok(())
+    }
+
+    // 将已发布的文章归档，归档后不再显示内容
+    pub fn archive(&mut self) {
+        self.fire(Event::Archive, "", self.clock.now());
+    }
+
+    pub fn try_archive(&mut self) -> Result<(), TransitionError> {
+        self.check(Action::Archive)?;
+        self.archive();
+        Ok(())
+    }
+
+    // 安排的发布时间，没有安排时为None
+    pub fn publish_at(&self) -> Option<u64> {
+        self.publish_at
+    }
+
+    // 时间推进到now：到达发布时间的Scheduled文章转为Published
+    pub fn tick(&mut self, now: u64) {
+        self.fire(Event::Tick, "", now);
+    }
+
+    // 按状态机的规则处理event。状态改变时执行进入新状态的动作，
+    // 并以actor的名义通知所有Listener；不接受的事件被忽略。
+    fn fire(&mut self, event: Event, actor: &str, at: u64) {
+        let facts = Facts {
+            approved: self.policy.is_satisfied(&self.approvals),
+            scheduled: self.publish_at.is_some(),
+            due: self.publish_at.is_some_and(|t| at >= t),
+        };
+        let from = self.state;
+        let to = match post_machine().next(from, event, &facts) {
+            Ok(to) if to != from => to,
+            _ => return,
+        };
+
+        self.state = to;
+        match to {
+            // 退回草稿后需要重新收集批准
+            PostState::Draft => {
+                self.approvals.clear();
+                self.publish_at = None;
+            }
+            PostState::Published => {
+                self.publish_at = None;
+                self.meta.published = Some(at);
+            }
+            _ => {}
+        }
+
+        let event = TransitionEvent {
+            from: from.name(),
+            to: to.name(),
+            actor: actor.to_string(),
+            at,
+        };
+        for listener in &self.listeners {
+            listener.on_transition(&event);
+        }
+    }
+
+    fn check(&self, action: Action) -> Result<(), TransitionError> {
+        if self.allows(action) {
+            Ok(())
+        } else {
+            Err(TransitionError {
+                state: self.state_name(),
+                action,
+                reason: None,
+            })
+        }
+    }
+}
+
+// 文章的全部数据，字段都是公开的。
+// 用于在Post与其他表示（如 new_blog 的各个类型）之间转换而不丢失任何数据。
+#[derive(Debug, Clone)]
+pub struct PostParts {
+    pub state: PostState,
+    pub approvals: Vec<Reviewer>,
+    pub publish_at: Option<u64>,
+    pub content: String,
+    pub rejections: Vec<String>,
+    pub author: String,
+    pub policy: ApprovalPolicy,
+    pub history: History,
+    pub meta: Metadata,
+    pub comments: Comments,
+}
+
+// 与Post::new()相同的一篇空白草稿
+impl Default for PostParts {
+    fn default() -> Self {
+        Post::new().into_parts()
     }
 }
 
-trait State {
-    fn request_review(self: Box<Self>) -> Box<dyn State>;
-    fn approve(self: Box<Self>) -> Box<dyn State>;
-    // 为content方法添加了默认的trait实现，它会返回一个空的字符串切片
-    // 使得我们可以不必在Draft和PendingReview结构体中重复实现content。
-    fn content<'a>(&self, _post: &'a Post) -> &'a str {
-        ""
+// PostParts 描述的文章不可能由状态机得到
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PartsError {
+    // Scheduled状态必须带有发布时间
+    MissingPublishAt,
+    // 只有PendingReview和Scheduled状态可以有发布时间
+    UnexpectedPublishAt(PostState),
+    // 退回草稿时批准会被清空
+    ApprovalsInDraft,
+    // slug会被用作文件名，不能包含 / 或 .. 之类的字符
+    InvalidSlug(String),
+}
+
+impl fmt::Display for PartsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PartsError::MissingPublishAt => write!(f, "scheduled post without publish_at"),
+            PartsError::UnexpectedPublishAt(state) => {
+                write!(f, "{} post with publish_at", state.name())
+            }
+            PartsError::ApprovalsInDraft => write!(f, "draft post with approvals"),
+            PartsError::InvalidSlug(slug) => write!(f, "invalid slug {:?}", slug),
+        }
     }
 }
 
-struct Draft {}
+impl Error for PartsError {}
+
+impl Post {
+    // 由各部分组装文章，Listener需要重新注册，时钟为系统时间。
+    // 只接受状态机进入各状态后会有的数据：发布时间和批准要与状态相符，slug要能用作文件名。
+    pub fn restore(parts: PostParts) -> Result<Post, PartsError> {
+        match parts.state {
+            PostState::Scheduled if parts.publish_at.is_none() => {
+                return Err(PartsError::MissingPublishAt)
+            }
+            PostState::Draft | PostState::Published | PostState::Archived
+                if parts.publish_at.is_some() =>
+            {
+                return Err(PartsError::UnexpectedPublishAt(parts.state))
+            }
+            PostState::Draft if !parts.approvals.is_empty() => {
+                return Err(PartsError::ApprovalsInDraft)
+            }
+            _ => {}
+        }
+        if !metadata::is_valid_slug(&parts.meta.slug) {
+            return Err(PartsError::InvalidSlug(parts.meta.slug));
+        }
 
-impl State for Draft {
-    // 只能被包裹着当前类型的Box实例调用
-    // 在调用过程中获取Box<Self>的所有权并使旧的状态失效，
-    // 从而将Post的状态值转换为一个新的状态。
-    fn request_review(self: Box<Self>) -> Box<dyn State> {
-        Box::new(PendingReview {})
+        Ok(Post {
+            state: parts.state,
+            approvals: parts.approvals,
+            publish_at: parts.publish_at,
+            content: parts.content,
+            rejections: parts.rejections,
+            author: parts.author,
+            policy: parts.policy,
+            history: parts.history,
+            meta: parts.meta,
+            comments: parts.comments,
+            listeners: Vec::new(),
+            clock: Arc::new(SystemClock),
+        })
     }
 
-    fn approve(self: Box<Self>) -> Box<dyn State> {
-        self
+    // 拆分为各部分，与restore互逆（Listener和时钟除外）
+    pub fn into_parts(self) -> PostParts {
+        PostParts {
+            state: self.state,
+            approvals: self.approvals,
+            publish_at: self.publish_at,
+            content: self.content,
+            rejections: self.rejections,
+            author: self.author,
+            policy: self.policy,
+            history: self.history,
+            meta: self.meta,
+            comments: self.comments,
+        }
     }
 }
 
-struct PendingReview {}
+// 文章所处的状态。各状态用到的数据（批准、发布时间）保存在Post中，
+// 状态之间的转换规则由post_machine集中定义。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostState {
+    Draft,
+    PendingReview,
+    // 已经批准、等待到达发布时间的文章
+    Scheduled,
+    Published,
+    // 下线的文章，不再接受任何操作
+    Archived,
+}
+
+impl PostState {
+    // 状态的名称，与Post::state_name相同
+    pub fn name(self) -> &'static str {
+        match self {
+            PostState::Draft => "draft",
+            PostState::PendingReview => "pending_review",
+            PostState::Scheduled => "scheduled",
+            PostState::Published => "published",
+            PostState::Archived => "archived",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<PostState> {
+        PostState::all().iter().copied().find(|s| s.name() == name)
+    }
 
-impl State for PendingReview {
-    fn request_review(self: Box<Self>) -> Box<dyn State> {
-        self
+    // 所有状态，按工作流中的顺序排列
+    pub fn all() -> &'static [PostState] {
+        post_machine().states()
     }
+}
+
+// 状态机的事件：用户的操作加上时间的推进
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Event {
+    RequestReview,
+    Approve,
+    Reject,
+    Schedule,
+    Archive,
+    Tick,
+}
 
-    fn approve(self: Box<Self>) -> Box<dyn State> {
-        Box::new(Published {})
+impl From<Action> for Event {
+    fn from(action: Action) -> Event {
+        match action {
+            Action::RequestReview => Event::RequestReview,
+            Action::Approve => Event::Approve,
+            Action::Reject => Event::Reject,
+            Action::Schedule => Event::Schedule,
+            Action::Archive => Event::Archive,
+        }
     }
 }
 
-struct Published {}
+// 守卫条件用到的事实，在触发事件之前由Post计算
+struct Facts {
+    // 已有的批准满足审批规则
+    approved: bool,
+    // 安排了发布时间
+    scheduled: bool,
+    // 已经到达发布时间
+    due: bool,
+}
+
+// 文章的审阅流程。规则表在第一次使用时构建并检查，之后所有文章共用
+fn post_machine() -> &'static StateMachine<PostState, Event, Facts> {
+    static MACHINE: OnceLock<StateMachine<PostState, Event, Facts>> = OnceLock::new();
+    MACHINE.get_or_init(|| {
+        use PostState::*;
+
+        StateMachine::builder(Draft)
+            .state(Draft)
+            .state(PendingReview)
+            .state(Scheduled)
+            .state(Published)
+            .state(Archived)
+            .transition(Draft, Event::RequestReview, PendingReview)
+            .guarded(PendingReview, Event::Approve, Scheduled, |f: &Facts| {
+                f.approved && f.scheduled
+            })
+            .guarded(PendingReview, Event::Approve, Published, |f: &Facts| {
+                f.approved
+            })
+            // 还没有满足审批规则，继续等待其他审阅者
+            .transition(PendingReview, Event::Approve, PendingReview)
+            .transition(PendingReview, Event::Reject, Draft)
+            .transition(PendingReview, Event::Schedule, PendingReview)
+            // 发布之前仍然可以撤回到草稿或改期
+            .transition(Scheduled, Event::Reject, Draft)
+            .transition(Scheduled, Event::Schedule, Scheduled)
+            .guarded(Scheduled, Event::Tick, Published, |f: &Facts| f.due)
+            .transition(Published, Event::Archive, Archived)
+            .build()
+            .expect("post state machine is well formed")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending() -> Post {
+        let mut post = Post::new();
+        post.add_text("I ate a salad for lunch today");
+        post.request_review();
+        post
+    }
+
+    #[test]
+    fn reject_draft_does_nothing() {
+        let mut post = Post::new();
+        post.reject("too short");
 
-impl State for Published {
-    fn request_review(self: Box<Self>) -> Box<dyn State> {
-        self
+        assert_eq!("draft", post.state_name());
+        assert!(post.rejections().is_empty());
     }
 
-    fn approve(self: Box<Self>) -> Box<dyn State> {
-        self
+    #[test]
+    fn reject_pending_review_returns_to_draft() {
+        let mut post = pending();
+        post.reject("needs a photo");
+
+        assert_eq!("draft", post.state_name());
+        assert_eq!(["needs a photo"], post.rejections());
+        assert_eq!("", post.content());
+
+        // 修改后可以再次提交审阅并发布
+        post.add_text(" with a photo");
+        post.request_review();
+        post.reject("photo is blurry");
+        post.request_review();
+        post.approve();
+        assert_eq!(["needs a photo", "photo is blurry"], post.rejections());
+        assert_eq!("I ate a salad for lunch today with a photo", post.content());
+    }
+
+    #[test]
+    fn reject_published_does_nothing() {
+        let mut post = pending();
+        post.approve();
+        post.reject("too late");
+
+        assert_eq!("published", post.state_name());
+        assert!(post.rejections().is_empty());
+        assert_eq!("I ate a salad for lunch today", post.content());
+    }
+
+    #[test]
+    fn every_edit_is_a_revision() {
+        let mut post = Post::with_author("alice");
+        post.add_text("first line\n");
+        post.edit("bob", "second line\n");
+        post.edit("alice", "third line\n");
+
+        let history = post.history();
+        assert_eq!(3, history.revisions().len());
+        assert_eq!("bob", post.revision(2).unwrap().author);
+        assert_eq!(
+            "first line\nsecond line\n",
+            post.revision(2).unwrap().content
+        );
+        assert!(post.revision(0).is_none());
+        assert!(post.revision(4).is_none());
+
+        let diff: Vec<String> = post
+            .diff(1, 3)
+            .unwrap()
+            .iter()
+            .map(|l| l.to_string())
+            .collect();
+        assert_eq!(vec![" first line", "+second line", "+third line"], diff);
+        assert!(post.diff(1, 9).is_none());
+    }
+
+    #[test]
+    fn line_diff() {
+        let old = "a\nb\nc\nd";
+        let new = "a\nc\nd\ne";
+        assert_eq!(
+            vec![
+                DiffLine::Same(String::from("a")),
+                DiffLine::Removed(String::from("b")),
+                DiffLine::Same(String::from("c")),
+                DiffLine::Same(String::from("d")),
+                DiffLine::Added(String::from("e")),
+            ],
+            revision::diff(old, new)
+        );
+    }
+
+    fn published() -> Post {
+        let mut post = Post::with_author("alice");
+        post.set_policy(ApprovalPolicy::approvals(1).with_role("editor"));
+        post.edit("alice", "line \"one\"\n");
+        post.edit("bob", "line two\n");
+        post.request_review();
+        post.reject("needs a title");
+        post.request_review();
+        post.approve_by(&Reviewer::new("carol").with_role("editor"))
+            .unwrap();
+        post
+    }
+
+    fn assert_same(expected: &Post, actual: &Post) {
+        assert_eq!(expected.state_name(), actual.state_name());
+        assert_eq!(expected.content, actual.content);
+        assert_eq!(expected.author(), actual.author());
+        assert_eq!(expected.rejections(), actual.rejections());
+        assert_eq!(expected.policy(), actual.policy());
+        assert_eq!(expected.approvals(), actual.approvals());
+        assert_eq!(expected.history(), actual.history());
+        assert_eq!(expected.metadata(), actual.metadata());
+    }
+
+    #[test]
+    fn encode_round_trips_every_state() {
+        let mut draft = Post::new();
+        draft.add_text("draft");
+        draft.set_title("A \"quoted\" title");
+        draft.add_tag("rust");
+        let mut review = pending();
+        review.set_policy(ApprovalPolicy::approvals(2));
+        review.approve_by(&Reviewer::new("bob")).unwrap();
+
+        for post in [draft, review, published()] {
+            let decoded = store::decode(&store::encode(&post)).unwrap();
+            assert_same(&post, &decoded);
+        }
+    }
+
+    #[test]
+    fn decode_rejects_bad_data() {
+        assert!(matches!(store::decode("{"), Err(StoreError::Corrupt(_))));
+        for number in ["inf", "NaN", "+1", ".5", "1.", "01", "-", "1e", "0x10"] {
+            assert_eq!(
+                Err(0),
+                json::parse(number).map(|_| ()),
+                "{} is not a JSON number",
+                number
+            );
+        }
+        for (text, number) in [("0", 0.0), ("-1.5", -1.5), ("2E+2", 200.0), ("1e-1", 0.1)] {
+            assert_eq!(Ok(json::Value::Number(number)), json::parse(text));
+        }
+
+        let text = store::encode(&Post::new()).replace("\"draft\"", "\"limbo\"");
+        assert!(matches!(store::decode(&text), Err(StoreError::Corrupt(_))));
+    }
+
+    #[test]
+    fn memory_repository() {
+        let mut repo = MemoryRepository::new();
+        let id = repo.insert(&pending()).unwrap();
+
+        let mut post = repo.get(id).unwrap();
+        post.approve();
+        repo.update(id, &post).unwrap();
+        assert_eq!("published", repo.get(id).unwrap().state_name());
+
+        assert_eq!(vec![id], repo.ids().unwrap());
+        repo.remove(id).unwrap();
+        assert!(matches!(repo.get(id), Err(StoreError::NotFound(_))));
+        assert!(matches!(
+            repo.update(id, &post),
+            Err(StoreError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn file_repository_resumes_across_runs() {
+        let dir = std::env::temp_dir().join(format!("blog-store-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let post = published();
+        let (first, second) = {
+            let mut repo = FileRepository::open(&dir).unwrap();
+            (
+                repo.insert(&Post::new()).unwrap(),
+                repo.insert(&post).unwrap(),
+            )
+        };
+        assert_eq!((1, 2), (first, second));
+
+        // 重新打开目录，相当于下一次运行
+        let mut repo = FileRepository::open(&dir).unwrap();
+        assert_eq!(vec![1, 2], repo.ids().unwrap());
+        assert_same(&post, &repo.get(second).unwrap());
+
+        repo.remove(first).unwrap();
+        assert_eq!(3, repo.insert(&Post::new()).unwrap());
+        assert!(matches!(repo.get(first), Err(StoreError::NotFound(1))));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn repositories_keep_slugs_unique() {
+        fn check(repo: &mut dyn PostRepository) {
+            let mut lunch = Post::new();
+            lunch.set_title("Lunch");
+            let first = repo.insert(&lunch).unwrap();
+            let second = repo.insert(&lunch).unwrap();
+            assert_eq!("lunch", repo.get(first).unwrap().slug());
+            assert_eq!("lunch-2", repo.get(second).unwrap().slug());
+
+            // 后来才有标题的文章在更新时去重，slug不变的更新保持原样
+            let third = repo.insert(&Post::new()).unwrap();
+            let mut post = repo.get(third).unwrap();
+            post.set_title("Lunch");
+            repo.update(third, &post).unwrap();
+            assert_eq!("lunch-3", repo.get(third).unwrap().slug());
+            let (post, version) = repo.get_versioned(second).unwrap();
+            repo.update_if(second, version, &post).unwrap();
+            assert_eq!("lunch-2", repo.get(second).unwrap().slug());
+
+            // 删除文章后它的slug可以再次使用
+            repo.remove(first).unwrap();
+            let fourth = repo.insert(&lunch).unwrap();
+            assert_eq!("lunch", repo.get(fourth).unwrap().slug());
+        }
+
+        check(&mut MemoryRepository::new());
+
+        let dir = std::env::temp_dir().join(format!("blog-slugs-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        check(&mut FileRepository::open(&dir).unwrap());
+
+        // 没有slug索引的旧目录在第一次插入时根据现有文章建立索引
+        std::fs::remove_dir_all(dir.join("slugs")).unwrap();
+        let mut repo = FileRepository::open(&dir).unwrap();
+        let mut lunch = Post::new();
+        lunch.set_title("Lunch");
+        let id = repo.insert(&lunch).unwrap();
+        assert_eq!("lunch-4", repo.get(id).unwrap().slug());
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 
-    fn content<'a>(&self, post: &'a Post) -> &'a str {
-        &post.content
+    #[test]
+    fn file_repository_never_reuses_ids() {
+        let dir = std::env::temp_dir().join(format!("blog-ids-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        {
+            let mut repo = FileRepository::open(&dir).unwrap();
+            assert_eq!(1, repo.insert(&Post::new()).unwrap());
+            assert_eq!(2, repo.insert(&Post::new()).unwrap());
+            // 删除最大的id后，这个id也不会再分配
+            repo.remove(2).unwrap();
+            assert_eq!(3, repo.insert(&Post::new()).unwrap());
+            repo.remove(3).unwrap();
+        }
+
+        let mut repo = FileRepository::open(&dir).unwrap();
+        assert_eq!(4, repo.insert(&Post::new()).unwrap());
+        assert_eq!(vec![1, 4], repo.ids().unwrap());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn concurrent_inserts_on_disk() {
+        use std::thread;
+
+        let dir = std::env::temp_dir().join(format!("blog-inserts-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        FileRepository::open(&dir).unwrap();
+
+        // 每个线程单独打开目录，相当于同时运行的多个进程
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                let dir = dir.clone();
+                thread::spawn(move || {
+                    let mut repo = FileRepository::open(&dir).unwrap();
+                    (0..10)
+                        .map(|i| {
+                            let mut post = Post::new();
+                            post.set_title("Lunch");
+                            post.add_text(&format!("{}-{}", t, i));
+                            repo.insert(&post).unwrap()
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        let mut ids: Vec<PostId> = handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect();
+        ids.sort_unstable();
+
+        assert_eq!((1..=40).collect::<Vec<_>>(), ids);
+        let repo = FileRepository::open(&dir).unwrap();
+        assert_eq!(ids, repo.ids().unwrap());
+        // 同时插入的同名文章也得到不同的slug
+        let mut slugs: Vec<String> = ids
+            .iter()
+            .map(|&id| repo.get(id).unwrap().slug().to_string())
+            .collect();
+        slugs.sort_unstable();
+        slugs.dedup();
+        assert_eq!(40, slugs.len());
+        // 没有留下临时文件和锁文件
+        let leftovers: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter(|name| !name.ends_with(".json") && name != "next_id" && name != "slugs")
+            .collect();
+        assert!(leftovers.is_empty(), "{:?}", leftovers);
+        assert_eq!(40, std::fs::read_dir(dir.join("slugs")).unwrap().count());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn stale_locks_are_broken() {
+        let dir = std::env::temp_dir().join(format!("blog-locks-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut repo = FileRepository::open(&dir).unwrap();
+        let id = repo.insert(&Post::new()).unwrap();
+
+        // 已经退出的进程留下的锁
+        let lock = dir.join(format!("{}.lock", id));
+        std::fs::write(&lock, "pid 4000000\ntime 0\n").unwrap();
+        repo.update(id, &published()).unwrap();
+        assert!(!lock.exists());
+
+        std::fs::write(&lock, "pid 4000000\ntime 0\n").unwrap();
+        std::fs::write(dir.join("repo.lock"), "pid 4000000\ntime 0\n").unwrap();
+        repo.remove(id).unwrap();
+        assert_eq!(2, repo.insert(&Post::new()).unwrap());
+        assert!(!lock.exists());
+        assert!(!dir.join("repo.lock").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // 读出文章后追加一行，遇到冲突时重新读取，返回发生冲突的次数
+    fn append_with_retry<R: PostRepository + ?Sized>(
+        repo: &mut R,
+        id: PostId,
+        line: &str,
+    ) -> usize {
+        let mut conflicts = 0;
+        loop {
+            let (mut post, version) = repo.get_versioned(id).unwrap();
+            post.add_text(line);
+            match repo.update_if(id, version, &post) {
+                Ok(_) => return conflicts,
+                Err(StoreError::Conflict { .. }) => conflicts += 1,
+                Err(e) => panic!("{}", e),
+            }
+        }
+    }
+
+    #[test]
+    fn stale_writes_are_rejected() {
+        let mut repo = MemoryRepository::new();
+        let id = repo.insert(&Post::new()).unwrap();
+
+        let (mut first, version) = repo.get_versioned(id).unwrap();
+        let (mut second, same) = repo.get_versioned(id).unwrap();
+        assert_eq!((1, 1), (version, same));
+
+        first.add_text("first");
+        assert_eq!(2, repo.update_if(id, version, &first).unwrap());
+
+        second.add_text("second");
+        let err = repo.update_if(id, same, &second).unwrap_err();
+        assert!(matches!(
+            err,
+            StoreError::Conflict {
+                id: 1,
+                expected: 1,
+                actual: 2
+            }
+        ));
+        assert_eq!(
+            "post 1 was modified concurrently: expected version 1, found 2",
+            err.to_string()
+        );
+        assert_eq!("first", repo.get(id).unwrap().into_parts().content);
+
+        // 不检查版本号的update也会增加版本号
+        repo.update(id, &second).unwrap();
+        assert_eq!(3, repo.get_versioned(id).unwrap().1);
+        assert!(matches!(
+            repo.update_if(2, 1, &second),
+            Err(StoreError::NotFound(2))
+        ));
+    }
+
+    #[test]
+    fn concurrent_edits_in_memory() {
+        use std::sync::Mutex;
+        use std::thread;
+
+        let repo = Arc::new(Mutex::new(MemoryRepository::new()));
+        let id = repo.lock().unwrap().insert(&Post::new()).unwrap();
+
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                let repo = Arc::clone(&repo);
+                thread::spawn(move || {
+                    for i in 0..25 {
+                        // 读和写分别加锁，中间其他线程可以写入，冲突由版本号发现
+                        loop {
+                            let (mut post, version) =
+                                repo.lock().unwrap().get_versioned(id).unwrap();
+                            post.add_text(&format!("{}-{}\n", t, i));
+                            thread::yield_now();
+                            match repo.lock().unwrap().update_if(id, version, &post) {
+                                Ok(_) => break,
+                                Err(StoreError::Conflict { .. }) => continue,
+                                Err(e) => panic!("{}", e),
+                            }
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let (post, version) = repo.lock().unwrap().get_versioned(id).unwrap();
+        assert_eq!(101, version);
+        let content = post.into_parts().content;
+        assert_eq!(100, content.lines().count());
+        for t in 0..4 {
+            for i in 0..25 {
+                assert!(content.lines().any(|l| l == format!("{}-{}", t, i)));
+            }
+        }
+    }
+
+    #[test]
+    fn concurrent_edits_on_disk() {
+        use std::thread;
+
+        let dir = std::env::temp_dir().join(format!("blog-versions-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let id = FileRepository::open(&dir)
+            .unwrap()
+            .insert(&Post::new())
+            .unwrap();
+
+        // 每个线程单独打开目录，相当于同时运行的多个进程
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                let dir = dir.clone();
+                thread::spawn(move || {
+                    let mut repo = FileRepository::open(&dir).unwrap();
+                    (0..10)
+                        .map(|i| append_with_retry(&mut repo, id, &format!("{}-{}\n", t, i)))
+                        .sum::<usize>()
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let repo = FileRepository::open(&dir).unwrap();
+        let (post, version) = repo.get_versioned(id).unwrap();
+        assert_eq!(41, version);
+        assert_eq!(40, post.into_parts().content.lines().count());
+        // 锁文件都已删除，也不会被当作文章
+        assert_eq!(vec![id], repo.ids().unwrap());
+        assert!(!dir.join(format!("{}.lock", id)).exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn try_approve_reports_refused_approvals() {
+        let mut post = pending();
+        post.comment("bob", "fix the intro", None).unwrap();
+        let err = post.try_approve().unwrap_err();
+        assert_eq!(Some(ApprovalError::UnresolvedComments(1)), err.reason);
+        assert_eq!(
+            "cannot approve a post that is pending_review: 1 comment is unresolved",
+            err.to_string()
+        );
+        assert_eq!("pending_review", post.state_name());
+        assert!(post.approvals().is_empty());
+
+        post.resolve_comment(1, "").unwrap();
+        assert_eq!(Ok(()), post.try_approve());
+        assert_eq!("published", post.state_name());
+
+        // 第二次匿名批准
+        let mut post = pending();
+        post.set_policy(ApprovalPolicy::approvals(2));
+        post.approve();
+        assert_eq!(
+            Some(ApprovalError::AlreadyApproved(String::new())),
+            post.try_approve().unwrap_err().reason
+        );
+
+        // 批准后仍不满足规则时不记录这次批准
+        let mut post = pending();
+        post.set_policy(ApprovalPolicy::approvals(1).with_role("legal"));
+        assert_eq!(
+            Some(ApprovalError::PolicyNotMet),
+            post.try_approve().unwrap_err().reason
+        );
+        assert!(post.approvals().is_empty());
+    }
+
+    #[test]
+    fn checked_transitions_report_state_and_action() {
+        let mut post = Post::new();
+        assert_eq!(
+            Err(TransitionError {
+                state: "draft",
+                action: Action::Approve,
+                reason: None,
+            }),
+            post.try_approve()
+        );
+        assert_eq!(
+            "cannot reject a post that is draft",
+            post.try_reject("no").unwrap_err().to_string()
+        );
+        assert!(post.rejections().is_empty());
+
+        assert_eq!(Ok(()), post.try_request_review());
+        assert_eq!(
+            Err(TransitionError {
+                state: "pending_review",
+                action: Action::RequestReview,
+                reason: None,
+            }),
+            post.try_request_review()
+        );
+
+        assert_eq!(Ok(()), post.try_reject("typo"));
+        assert_eq!(Ok(()), post.try_request_review());
+        assert_eq!(Ok(()), post.try_approve());
+        assert_eq!("published", post.state_name());
+
+        for action in [Action::RequestReview, Action::Approve, Action::Reject] {
+            assert!(!post.allows(action));
+        }
+        assert_eq!(
+            "cannot request review of a post that is published",
+            post.try_request_review().unwrap_err().to_string()
+        );
+
+        // 宽松的方法仍然静默忽略非法操作
+        post.request_review();
+        post.reject("ignored");
+        assert_eq!("published", post.state_name());
+        assert_eq!(["typo"], post.rejections());
+    }
+
+    #[test]
+    fn scheduled_post_publishes_on_tick() {
+        let clock = ManualClock::new(1_000);
+        let mut post = pending();
+
+        post.schedule(1_060);
+        assert_eq!(Some(1_060), post.publish_at());
+        post.approve();
+        assert_eq!("scheduled", post.state_name());
+        assert_eq!("", post.content());
+
+        post.tick(clock.now());
+        assert_eq!("scheduled", post.state_name());
+
+        clock.advance(60);
+        post.tick(clock.now());
+        assert_eq!("published", post.state_name());
+        assert_eq!("I ate a salad for lunch today", post.content());
+        assert_eq!(None, post.publish_at());
+    }
+
+    #[test]
+    fn scheduled_post_can_be_pulled_back() {
+        let mut post = pending();
+        post.try_schedule(50).unwrap();
+        post.approve();
+
+        // 到达发布时间前可以改期或撤回
+        post.try_schedule(100).unwrap();
+        assert_eq!(Some(100), post.publish_at());
+        assert!(post.try_archive().is_err());
+        post.try_reject("wrong date").unwrap();
+        assert_eq!("draft", post.state_name());
+        assert_eq!(None, post.publish_at());
+
+        assert_eq!(
+            "cannot schedule a post that is draft",
+            post.try_schedule(10).unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn archive_published_post() {
+        let mut post = pending();
+        post.approve();
+        post.try_archive().unwrap();
+
+        assert_eq!("archived", post.state_name());
+        assert_eq!("", post.content());
+        for action in [
+            Action::RequestReview,
+            Action::Approve,
+            Action::Reject,
+            Action::Schedule,
+            Action::Archive,
+        ] {
+            assert!(!post.allows(action));
+        }
+
+        let decoded = store::decode(&store::encode(&post)).unwrap();
+        assert_eq!("archived", decoded.state_name());
+    }
+
+    #[test]
+    fn publish_due_uses_clock() {
+        let clock = ManualClock::new(0);
+        let mut repo = MemoryRepository::new();
+
+        let mut early = pending();
+        early.schedule(10);
+        early.approve();
+        let mut late = pending();
+        late.schedule(20);
+        late.approve();
+
+        let early = repo.insert(&early).unwrap();
+        let late = repo.insert(&late).unwrap();
+        let draft = repo.insert(&Post::new()).unwrap();
+
+        // 保存后再读取，Scheduled状态和发布时间保持不变
+        assert_eq!(Some(20), repo.get(late).unwrap().publish_at());
+        assert!(store::publish_due(&mut repo, &clock).unwrap().is_empty());
+
+        clock.set(15);
+        assert_eq!(vec![early], store::publish_due(&mut repo, &clock).unwrap());
+        clock.advance(10);
+        assert_eq!(vec![late], store::publish_due(&mut repo, &clock).unwrap());
+        assert_eq!("published", repo.get(late).unwrap().state_name());
+        assert_eq!("draft", repo.get(draft).unwrap().state_name());
+    }
+
+    fn command(line: &str) -> cli::Command {
+        let args: Vec<String> = line.split(' ').map(String::from).collect();
+        cli::Config::new(&args).unwrap().command
+    }
+
+    #[test]
+    fn cli_workflow() {
+        let mut repo = MemoryRepository::new();
+        let clock = Arc::new(ManualClock::new(0));
+        let mut exec =
+            |line: &str| cli::execute(&mut repo, &command(line), &clock).map_err(|e| e.to_string());
+
+        assert_eq!(
+            Ok(String::from("1\n")),
+            exec("blog new --author alice hello")
+        );
+        assert_eq!(Ok(String::new()), exec("blog edit 1 world"));
+        assert_eq!(
+            Err(String::from("post 1: cannot reject a post that is draft")),
+            exec("blog reject 1 no")
+        );
+        assert_eq!(
+            Ok(String::from("post 1 is pending_review\n")),
+            exec("blog submit 1")
+        );
+        assert_eq!(
+            Err(String::from(
+                "cannot edit post 1: it is pending_review, not draft"
+            )),
+            exec("blog edit 1 more")
+        );
+        assert_eq!(
+            Err(String::from(
+                "cannot approve post 1: alice cannot approve their own post"
+            )),
+            exec("blog approve 1 --as alice")
+        );
+        assert_eq!(
+            Ok(String::from("post 1 is published\n")),
+            exec("blog approve 1 --as bob")
+        );
+        assert_eq!(
+            Err(String::from(
+                "post 1: cannot request review of a post that is published"
+            )),
+            exec("blog submit 1")
+        );
+        assert_eq!(Ok(String::from("2\n")), exec("blog new draft"));
+
+        assert_eq!(
+            Ok(String::from(
+                "post 1 [published] by alice\napproved by: bob\n---\nhelloworld\n"
+            )),
+            exec("blog show 1")
+        );
+        assert_eq!(
+            Ok(String::from("1\tpublished\talice\thelloworld\n")),
+            exec("blog list --state published")
+        );
+        assert_eq!(
+            Ok(String::from("2\tdraft\t\tdraft\n")),
+            exec("blog list --state draft")
+        );
+        assert_eq!(
+            Err(String::from("post 9 does not exist")),
+            exec("blog show 9")
+        );
+
+        assert_eq!(
+            Ok(String::from("post 1 is archived\n")),
+            exec("blog archive 1")
+        );
+        exec("blog submit 2").unwrap();
+        assert_eq!(
+            Ok(String::from("post 2 is pending_review\n")),
+            exec("blog schedule 2 0")
+        );
+        assert_eq!(
+            Ok(String::from("post 2 is scheduled\n")),
+            exec("blog approve 2 --as bob")
+        );
+        assert_eq!(Ok(String::from("post 2 is published\n")), exec("blog tick"));
+        assert_eq!(Ok(String::new()), exec("blog tick"));
+    }
+
+    #[test]
+    fn cli_argument_errors() {
+        let parse = |line: &str| {
+            let args: Vec<String> = line.split(' ').map(String::from).collect();
+            cli::Config::new(&args).err()
+        };
+
+        assert_eq!(
+            Some(String::from("missing command")),
+            parse("blog --store /tmp")
+        );
+        assert_eq!(
+            Some(String::from("unknown command publish")),
+            parse("blog publish 1")
+        );
+        assert_eq!(
+            Some(String::from("invalid post id x")),
+            parse("blog show x")
+        );
+        assert_eq!(
+            Some(String::from("approve requires --as NAME")),
+            parse("blog approve 1")
+        );
+        assert_eq!(
+            Some(String::from("wrong number of arguments for submit")),
+            parse("blog submit")
+        );
+        assert_eq!(
+            Some(String::from(
+                "unknown state live, expected one of draft, pending_review, scheduled, published, archived"
+            )),
+            parse("blog list --state live")
+        );
+        assert_eq!(
+            Some(String::from("invalid line range 2-x, expected N or N-M")),
+            parse("blog comment 1 hi --as bob --lines 2-x")
+        );
+        assert_eq!(
+            Some(String::from("reply requires --as NAME")),
+            parse("blog reply 1 1 hi")
+        );
+
+        let args: Vec<String> = ["blog", "--store", "/tmp/posts", "show", "3"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let config = cli::Config::new(&args).unwrap();
+        assert_eq!(std::path::PathBuf::from("/tmp/posts"), config.store);
+        assert_eq!(cli::Command::Show { id: 3 }, config.command);
+
+        // -- 之后的参数即使以 -- 开头也是位置参数
+        assert_eq!(
+            cli::Command::Edit {
+                id: 1,
+                author: Some(String::from("bob")),
+                text: String::from("--flag"),
+            },
+            command("blog --author bob edit -- 1 --flag")
+        );
+        assert_eq!(
+            Some(String::from("unknown option --flag")),
+            parse("blog edit 1 --flag")
+        );
+        assert_eq!(
+            cli::Command::List {
+                state: Some(PostState::Scheduled),
+                tag: None,
+                author: None,
+            },
+            command("blog list --state scheduled")
+        );
+
+        // 每个命令只接受自己的选项
+        assert_eq!(
+            Some(String::from("archive does not accept --as")),
+            parse("blog archive 1 --as bob")
+        );
+        assert_eq!(
+            Some(String::from("show does not accept --lines")),
+            parse("blog show 1 --lines 2")
+        );
+        assert_eq!(
+            Some(String::from("edit does not accept --tag")),
+            parse("blog edit 1 text --tag rust")
+        );
+        assert_eq!(
+            cli::Command::Reject {
+                id: 1,
+                reviewer: Some(String::from("bob")),
+                reason: String::from("typo"),
+            },
+            command("blog --store /tmp reject 1 typo --as bob")
+        );
+    }
+
+    #[test]
+    fn two_distinct_approvers() {
+        let mut post = Post::with_author("alice");
+        post.set_policy(ApprovalPolicy::approvals(2));
+        post.add_text("hello");
+        post.request_review();
+
+        let bob = Reviewer::new("bob");
+        assert_eq!(Ok(()), post.approve_by(&bob));
+        assert_eq!("pending_review", post.state_name());
+        assert_eq!(
+            Err(ApprovalError::AlreadyApproved(String::from("bob"))),
+            post.approve_by(&bob)
+        );
+        assert_eq!(
+            Err(ApprovalError::SelfApproval(String::from("alice"))),
+            post.approve_by(&Reviewer::new("alice"))
+        );
+
+        assert_eq!(Ok(()), post.approve_by(&Reviewer::new("carol")));
+        assert_eq!("published", post.state_name());
+        assert_eq!("hello", post.content());
+
+        let names: Vec<&str> = post.approvals().iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(vec!["bob", "carol"], names);
+        assert_eq!(
+            Err(ApprovalError::NotPendingReview),
+            post.approve_by(&Reviewer::new("dave"))
+        );
+    }
+
+    #[test]
+    fn required_role() {
+        let mut post = Post::with_author("alice");
+        post.set_policy(ApprovalPolicy::approvals(1).with_role("legal"));
+        post.request_review();
+
+        post.approve_by(&Reviewer::new("bob")).unwrap();
+        assert_eq!("pending_review", post.state_name());
+
+        // 匿名批准无法满足角色要求
+        post.approve();
+        assert_eq!("pending_review", post.state_name());
+
+        post.approve_by(&Reviewer::new("carol").with_role("legal"))
+            .unwrap();
+        assert_eq!("published", post.state_name());
+    }
+
+    #[test]
+    fn self_approval_when_allowed() {
+        let mut post = Post::with_author("alice");
+        post.set_policy(ApprovalPolicy::default().allow_self_approval());
+        post.request_review();
+
+        post.approve_by(&Reviewer::new("alice")).unwrap();
+        assert_eq!("published", post.state_name());
+    }
+
+    #[test]
+    fn reject_discards_approvals() {
+        let mut post = Post::new();
+        post.set_policy(ApprovalPolicy::approvals(2));
+        post.request_review();
+        post.approve_by(&Reviewer::new("bob")).unwrap();
+
+        post.reject("typo in title");
+        post.request_review();
+        assert!(post.approvals().is_empty());
+
+        post.approve_by(&Reviewer::new("carol")).unwrap();
+        assert_eq!("pending_review", post.state_name());
+    }
+
+    #[test]
+    fn render_html_only_when_published() {
+        let mut post = Post::new();
+        post.add_text("# Lunch\n\nI ate a *salad*.\n");
+        assert_eq!("", post.render_html());
+
+        post.request_review();
+        assert_eq!("", post.render_html());
+
+        post.approve();
+        assert_eq!(
+            "<h1>Lunch</h1>\n<p>I ate a <em>salad</em>.</p>\n",
+            post.render_html()
+        );
+    }
+
+    #[test]
+    fn markdown_blocks_and_inlines() {
+        let text = "\
+## Tips & tricks
+
+Use **bold**, `a < b` and [the book](https://doc.rust-lang.org/book/?a=1&b=2).
+Second line of the same paragraph.
+
+- one
+- two _three_
+
+1. first
+2. second
+
+```rust
+fn main() { println!(\"<hi>\"); }
+```
+";
+        let expected = "\
+<h2>Tips &amp; tricks</h2>
+<p>Use <strong>bold</strong>, <code>a &lt; b</code> and \
+<a href=\"https://doc.rust-lang.org/book/?a=1&amp;b=2\">the book</a>.
+Second line of the same paragraph.</p>
+<ul>
+<li>one</li>
+<li>two <em>three</em></li>
+</ul>
+<ol>
+<li>first</li>
+<li>second</li>
+</ol>
+<pre><code class=\"language-rust\">fn main() { println!(&quot;&lt;hi&gt;&quot;); }
+</code></pre>
+";
+        assert_eq!(expected, markdown::to_html(text));
+    }
+
+    #[test]
+    fn markdown_escapes_raw_html_and_unclosed_markers() {
+        assert_eq!(
+            "<p>&lt;script&gt;alert(1)&lt;/script&gt; 2 * 3 = 6</p>\n",
+            markdown::to_html("<script>alert(1)</script> 2 * 3 = 6")
+        );
+        assert_eq!("<p>a *b*</p>\n", markdown::to_html("a \\*b\\*"));
+        // 单词中间的 _ 不是强调
+        assert_eq!(
+            "<p>call snake_case_name and a__b__c or <em>this</em></p>\n",
+            markdown::to_html("call snake_case_name and a__b__c or _this_")
+        );
+        assert_eq!(
+            "<p><strong>bold_text</strong> x_ <em>y</em></p>\n",
+            markdown::to_html("__bold_text__ x_ _y_")
+        );
+    }
+
+    #[test]
+    fn markdown_link_labels_end_at_the_matching_bracket() {
+        assert_eq!(
+            "<p>[a] text <a href=\"url\">b</a></p>\n",
+            markdown::to_html("[a] text [b](url)")
+        );
+        assert_eq!(
+            "<p><a href=\"url\">see [1]</a> [x] (y)</p>\n",
+            markdown::to_html("[see [1]](url) [x] (y)")
+        );
+    }
+
+    #[test]
+    fn markdown_links_only_allow_safe_schemes() {
+        assert_eq!(
+            "<p><a href=\"mailto:a@example.com\">mail</a> \
+             <a href=\"HTTP://example.com\">web</a> \
+             <a href=\"../tags/food.html\">food</a> \
+             <a href=\"/a:b?c=d:e#f:g\">path</a></p>\n",
+            markdown::to_html(
+                "[mail](mailto:a@example.com) [web](HTTP://example.com) \
+                 [food](../tags/food.html) [path](/a:b?c=d:e#f:g)"
+            )
+        );
+        assert_eq!(
+            "<p>click <em>me</em> or this</p>\n",
+            markdown::to_html("click [*me*](javascript:alert%281%29) or [this](JavaScript:void)")
+        );
+        assert_eq!(
+            "<p>data</p>\n",
+            markdown::to_html("[data](data:text/html;base64,PHNjcmlwdD4=)")
+        );
+    }
+
+    #[test]
+    fn slugs_from_titles() {
+        assert_eq!("hello-world", metadata::slugify("Hello, World!"));
+        assert_eq!(
+            "rust-2024-edition",
+            metadata::slugify("  Rust 2024 -- edition ")
+        );
+        assert_eq!("post", metadata::slugify("?!"));
+
+        let taken = ["lunch", "lunch-2"];
+        assert_eq!(
+            "lunch-3",
+            metadata::unique_slug("Lunch", |s| taken.contains(&s))
+        );
+    }
+
+    #[test]
+    fn metadata_tracks_title_tags_and_times() {
+        let mut post = Post::with_author("alice");
+        assert!(post.created() > 0);
+        assert_eq!(post.created(), post.updated());
+
+        post.set_title("First post");
+        post.set_title("First post, revised");
+        assert_eq!("first-post", post.slug());
+
+        post.add_tag("rust");
+        post.add_tag("Rust");
+        post.add_tag("food");
+        assert_eq!(["rust", "food"], post.tags());
+        post.remove_tag("RUST");
+        assert_eq!(["food"], post.tags());
+        assert!(post.updated() >= post.created());
+    }
+
+    #[test]
+    fn collection_filters_and_searches() {
+        let mut posts = Collection::new();
+
+        let mut salad = Post::with_author("alice");
+        salad.set_title("Lunch");
+        salad.add_tag("food");
+        salad.add_text("I ate a salad for lunch today");
+        salad.request_review();
+        salad.approve_by(&Reviewer::new("bob")).unwrap();
+
+        let mut soup = Post::with_author("bob");
+        soup.set_title("Lunch");
+        soup.add_tag("Food");
+        soup.add_text("I ate a soup for lunch today");
+
+        assert_eq!("lunch", posts.insert(salad));
+        assert_eq!("lunch-2", posts.insert(soup));
+        assert_eq!(2, posts.len());
+
+        let slugs = |found: Vec<&Post>| -> Vec<String> {
+            found.iter().map(|p| p.slug().to_string()).collect()
+        };
+        assert_eq!(vec!["lunch", "lunch-2"], slugs(posts.by_tag("food")));
+        assert_eq!(vec!["lunch-2"], slugs(posts.by_author("bob")));
+        assert_eq!(vec!["lunch"], slugs(posts.by_state("published")));
+
+        // 只搜索已发布的文章，所有词都要出现
+        assert_eq!(vec!["lunch"], slugs(posts.search("LUNCH today")));
+        assert!(posts.search("soup").is_empty());
+        assert!(posts.search("salad soup").is_empty());
+        assert!(posts.search("  ").is_empty());
+
+        posts.get_mut("lunch-2").unwrap().request_review();
+        posts
+            .get_mut("lunch-2")
+            .unwrap()
+            .approve_by(&Reviewer::new("carol"))
+            .unwrap();
+        assert_eq!(vec!["lunch-2"], slugs(posts.search("soup")));
+
+        assert!(posts.remove("lunch").is_some());
+        assert!(posts.get("lunch").is_none());
+    }
+
+    #[test]
+    fn decode_without_metadata_uses_history_times() {
+        let mut post = Post::new();
+        post.add_text("old");
+        let timestamp = post.history().latest().unwrap().timestamp;
+
+        let text = store::encode(&post);
+        let text: String = text
+            .lines()
+            .filter(|line| {
+                ![
+                    "\"title\"",
+                    "\"slug\"",
+                    "\"tags\"",
+                    "\"created\"",
+                    "\"updated\"",
+                ]
+                .iter()
+                .any(|key| line.trim_start().starts_with(key))
+            })
+            .map(|line| format!("{}\n", line))
+            .collect();
+
+        let decoded = store::decode(&text).unwrap();
+        assert_eq!("", decoded.title());
+        assert_eq!(
+            (timestamp, timestamp),
+            (decoded.created(), decoded.updated())
+        );
+    }
+
+    #[test]
+    fn cli_metadata() {
+        let mut repo = MemoryRepository::new();
+        let clock = Arc::new(ManualClock::new(0));
+        let mut exec =
+            |line: &str| cli::execute(&mut repo, &command(line), &clock).map_err(|e| e.to_string());
+
+        exec("blog new --author alice --title Lunch --tag food --tag rust salad").unwrap();
+        exec("blog new --author bob --title Lunch --tag food soup").unwrap();
+        exec("blog new --author bob notitle").unwrap();
+
+        assert_eq!(
+            Ok(String::from(
+                "post 2 [draft] by bob\ntitle: Lunch\nslug: lunch-2\ntags: food\n---\nsoup\n"
+            )),
+            exec("blog show 2")
+        );
+        assert_eq!(
+            Ok(String::from(
+                "2\tdraft\tbob\tLunch\n3\tdraft\tbob\tnotitle\n"
+            )),
+            exec("blog list --author bob")
+        );
+        assert_eq!(
+            Ok(String::from("1\tdraft\talice\tLunch\n")),
+            exec("blog list --tag RUST")
+        );
+
+        assert_eq!(Ok(String::new()), exec("blog search salad"));
+        exec("blog submit 1").unwrap();
+        exec("blog approve 1 --as bob").unwrap();
+        assert_eq!(
+            Ok(String::from("1\tpublished\talice\tLunch\n")),
+            exec("blog search salad")
+        );
+    }
+
+    #[test]
+    fn restore_rejects_parts_the_state_machine_cannot_produce() {
+        let scheduled = PostParts {
+            state: PostState::Scheduled,
+            ..PostParts::default()
+        };
+        assert_eq!(
+            Some(PartsError::MissingPublishAt),
+            Post::restore(scheduled).err()
+        );
+
+        let published = PostParts {
+            state: PostState::Published,
+            publish_at: Some(10),
+            ..PostParts::default()
+        };
+        assert_eq!(
+            Some(PartsError::UnexpectedPublishAt(PostState::Published)),
+            Post::restore(published).err()
+        );
+
+        let draft = PostParts {
+            approvals: vec![Reviewer::new("bob")],
+            ..PostParts::default()
+        };
+        let err = Post::restore(draft).err().unwrap();
+        assert_eq!("draft post with approvals", err.to_string());
+
+        let mut post = pending();
+        post.schedule(10);
+        let restored = Post::restore(post.clone().into_parts()).unwrap();
+        assert_eq!(Some(10), restored.publish_at());
+        assert_eq!(post.state(), restored.state());
+    }
+
+    #[test]
+    fn timestamps_come_from_the_post_clock() {
+        let clock = Arc::new(ManualClock::new(100));
+        let log = Arc::new(AuditLog::new());
+        let mut post = Post::with_clock(clock.clone());
+        post.subscribe(log.clone());
+        assert_eq!(100, post.created());
+
+        clock.set(200);
+        post.edit("alice", "hello");
+        assert_eq!(200, post.revision(1).unwrap().timestamp);
+        assert_eq!(200, post.updated());
+
+        clock.set(300);
+        post.set_title("Hello");
+        assert_eq!(300, post.updated());
+
+        clock.set(400);
+        post.request_review();
+        let comment = post.comment("bob", "typo", None).unwrap();
+        assert_eq!(400, post.comments().get(comment).unwrap().timestamp);
+        post.resolve_comment(comment, "").unwrap();
+
+        clock.set(500);
+        post.approve();
+        assert_eq!(Some(500), post.published());
+        let at: Vec<u64> = log.entries().iter().map(|e| e.at).collect();
+        assert_eq!(vec![400, 500], at);
+
+        // 换成另一个时钟后，之后的时间都取自新的时钟
+        post.set_clock(Arc::new(ManualClock::new(900)));
+        post.archive();
+        assert_eq!(900, log.entries()[2].at);
+    }
+
+    #[test]
+    fn listeners_receive_every_transition() {
+        let log = Arc::new(AuditLog::new());
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        let mut post = Post::with_author("alice");
+        post.set_policy(ApprovalPolicy::approvals(2));
+        post.subscribe(log.clone());
+        let sink = Arc::clone(&seen);
+        post.subscribe(Arc::new(move |e: &TransitionEvent| {
+            sink.lock().unwrap().push(format!("{}>{}", e.from, e.to))
+        }));
+
+        post.add_text("hello");
+        post.approve();
+        post.request_review();
+        post.reject_by(&Reviewer::new("bob"), "too short");
+        post.request_review();
+        post.schedule(100);
+        post.approve_by(&Reviewer::new("bob")).unwrap();
+        // 安排发布时间和还差一次的批准都不改变状态，不产生事件
+        assert_eq!(3, log.len());
+        post.approve_by(&Reviewer::new("carol")).unwrap();
+        post.tick(99);
+        post.tick(100);
+        post.archive();
+
+        assert_eq!(
+            vec![
+                "draft>pending_review",
+                "pending_review>draft",
+                "draft>pending_review",
+                "pending_review>scheduled",
+                "scheduled>published",
+                "published>archived",
+            ],
+            *seen.lock().unwrap()
+        );
+
+        let entries = log.entries();
+        let actors: Vec<&str> = entries.iter().map(|e| e.actor.as_str()).collect();
+        assert_eq!(vec!["alice", "bob", "alice", "carol", "", ""], actors);
+        assert_eq!(100, entries[4].at);
+
+        let dump = log.dump();
+        assert_eq!(6, dump.lines().count());
+        assert_eq!(Some("100\tscheduled -> published\t"), dump.lines().nth(4));
+    }
+
+    #[test]
+    fn cloned_posts_share_listeners() {
+        let log = Arc::new(AuditLog::new());
+        let mut post = Post::new();
+        post.subscribe(log.clone());
+
+        let mut copy = post.clone();
+        copy.request_review();
+        assert_eq!(1, log.len());
+
+        // 从仓库读出的文章没有Listener
+        let decoded = store::decode(&store::encode(&copy)).unwrap();
+        assert!(decoded.listeners.is_empty());
+    }
+
+    // 与文章无关的工单流程，验证状态机可以单独使用
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Ticket {
+        Open,
+        Assigned,
+        Closed,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum TicketEvent {
+        Assign,
+        Close,
+        Reopen,
+    }
+
+    #[test]
+    fn generic_machine_with_guards() {
+        use machine::FireError;
+
+        // 上下文为是否已经写了解决方案
+        let tickets = StateMachine::builder(Ticket::Open)
+            .state(Ticket::Open)
+            .state(Ticket::Assigned)
+            .state(Ticket::Closed)
+            .transition(Ticket::Open, TicketEvent::Assign, Ticket::Assigned)
+            .guarded(
+                Ticket::Assigned,
+                TicketEvent::Close,
+                Ticket::Closed,
+                |solved: &bool| *solved,
+            )
+            .transition(Ticket::Closed, TicketEvent::Reopen, Ticket::Open)
+            .build()
+            .unwrap();
+
+        assert_eq!(Ticket::Open, tickets.initial());
+        assert!(tickets.accepts(Ticket::Assigned, TicketEvent::Close));
+        assert!(!tickets.accepts(Ticket::Open, TicketEvent::Close));
+
+        assert_eq!(
+            Ok(Ticket::Assigned),
+            tickets.next(Ticket::Open, TicketEvent::Assign, &false)
+        );
+        assert_eq!(
+            Err(FireError::GuardRejected {
+                state: Ticket::Assigned,
+                event: TicketEvent::Close
+            }),
+            tickets.next(Ticket::Assigned, TicketEvent::Close, &false)
+        );
+        assert_eq!(
+            Ok(Ticket::Closed),
+            tickets.next(Ticket::Assigned, TicketEvent::Close, &true)
+        );
+
+        let err = tickets
+            .next(Ticket::Open, TicketEvent::Reopen, &true)
+            .unwrap_err();
+        assert_eq!("no transition on Reopen from Open", err.to_string());
+    }
+
+    #[test]
+    fn machine_definition_is_validated() {
+        use machine::DefinitionError;
+
+        let base = || {
+            StateMachine::<Ticket, TicketEvent, ()>::builder(Ticket::Open)
+                .state(Ticket::Open)
+                .state(Ticket::Assigned)
+        };
+
+        assert_eq!(
+            Some(DefinitionError::UnknownState(Ticket::Closed)),
+            base()
+                .transition(Ticket::Open, TicketEvent::Close, Ticket::Closed)
+                .build()
+                .err()
+        );
+        assert_eq!(
+            Some(DefinitionError::DuplicateState(Ticket::Open)),
+            base().state(Ticket::Open).build().err()
+        );
+        assert_eq!(
+            Some(DefinitionError::Unreachable(Ticket::Assigned)),
+            base().build().err()
+        );
+
+        let shadowed = base()
+            .transition(Ticket::Open, TicketEvent::Assign, Ticket::Assigned)
+            .guarded(Ticket::Open, TicketEvent::Assign, Ticket::Open, |_| true)
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(
+            DefinitionError::Shadowed {
+                from: Ticket::Open,
+                event: TicketEvent::Assign
+            },
+            shadowed
+        );
+        assert_eq!(
+            "transition on Assign from Open can never fire: an earlier one has no guard",
+            shadowed.to_string()
+        );
+    }
+
+    #[test]
+    fn post_workflow_is_a_state_machine() {
+        let machine = post_machine();
+        assert_eq!(PostState::Draft, machine.initial());
+        assert_eq!(5, machine.states().len());
+        assert!(machine.accepts(PostState::Scheduled, Event::Tick));
+        assert!(!machine.accepts(PostState::Archived, Event::RequestReview));
+    }
+
+    #[test]
+    fn unresolved_comments_block_approval() {
+        let mut post = Post::with_author("alice");
+        post.add_text("line one\nline two\nline three\n");
+        assert_eq!(
+            Err(CommentError::NotPendingReview),
+            post.comment("bob", "too early", None)
+        );
+        post.request_review();
+
+        let first = post
+            .comment("bob", "expand this", Some(LineRange::new(2, 3)))
+            .unwrap();
+        let reply = post.reply(first, "alice", "will do").unwrap();
+        assert_eq!(
+            Err(CommentError::InvalidLines {
+                lines: LineRange::new(3, 4),
+                line_count: 3
+            }),
+            post.comment("bob", "past the end", Some(LineRange::new(3, 4)))
+        );
+        assert_eq!(
+            Err(CommentError::NotFound(9)),
+            post.reply(9, "bob", "nothing to reply to")
+        );
+        post.comment("carol", "nice", None).unwrap();
+
+        assert_eq!(
+            Err(ApprovalError::UnresolvedComments(2)),
+            post.approve_by(&Reviewer::new("bob"))
+        );
+
+        // 只有作者可以解决；解决回复等同于解决整个讨论串
+        assert_eq!(
+            Err(CommentError::NotAuthor(String::from("bob"))),
+            post.resolve_comment(first, "bob")
+        );
+        post.resolve_comment(reply, "alice").unwrap();
+        assert_eq!(1, post.comments().unresolved().count());
+
+        // 退回草稿后评论仍然保留，作者可以在草稿中解决
+        post.reject("address the comments");
+        assert_eq!(
+            Err(CommentError::NotPendingReview),
+            post.reply(first, "bob", "ping")
+        );
+        post.resolve_comment(3, "alice").unwrap();
+        post.request_review();
+        post.approve_by(&Reviewer::new("bob")).unwrap();
+        assert_eq!("published", post.state_name());
+
+        let decoded = store::decode(&store::encode(&post)).unwrap();
+        assert_eq!(post.comments(), decoded.comments());
+        assert_eq!(Some(first), decoded.comments().get(reply).unwrap().parent);
+    }
+
+    #[test]
+    fn cli_comments() {
+        let mut repo = MemoryRepository::new();
+        let clock = Arc::new(ManualClock::new(0));
+        let mut exec =
+            |line: &str| cli::execute(&mut repo, &command(line), &clock).map_err(|e| e.to_string());
+
+        exec("blog new --author alice one").unwrap();
+        assert_eq!(
+            Err(String::from(
+                "cannot comment on post 1: post is not pending review"
+            )),
+            exec("blog comment 1 early --as bob")
+        );
+        exec("blog submit 1").unwrap();
+        assert_eq!(
+            Ok(String::from("comment 1\n")),
+            exec("blog comment 1 why? --as bob --lines 1")
+        );
+        assert_eq!(
+            Ok(String::from("comment 2\n")),
+            exec("blog reply 1 1 because --as alice")
+        );
+        assert_eq!(
+            Err(String::from(
+                "cannot approve post 1: 1 comment is unresolved"
+            )),
+            exec("blog approve 1 --as bob")
+        );
+        assert_eq!(
+            Err(String::from(
+                "cannot resolve comment on post 1: bob is not the author and cannot resolve comments"
+            )),
+            exec("blog resolve 1 1 --as bob")
+        );
+        assert_eq!(Ok(String::new()), exec("blog resolve 1 2"));
+        assert_eq!(
+            Ok(String::from(
+                "post 1 [pending_review] by alice\ncomments:\n  #1 bob (line 1) [resolved]: why?\n    #2 alice: because\n---\none\n"
+            )),
+            exec("blog show 1")
+        );
+        assert_eq!(
+            Ok(String::from("post 1 is published\n")),
+            exec("blog approve 1 --as bob")
+        );
+    }
+
+    #[test]
+    fn site_dates_and_templates() {
+        assert_eq!("1970-01-01", site::date(0));
+        assert_eq!("2000-02-29T12:34:56Z", site::timestamp(951_827_696));
+        assert_eq!("2024-12-31T23:59:59Z", site::timestamp(1_735_689_599));
+
+        assert_eq!(
+            "<b>{{x}}</b> {{missing}} {{",
+            site::render("<b>{{ name }}</b> {{missing}} {{", &[("name", "{{x}}")])
+        );
+    }
+
+    fn published_at(title: &str, tags: &[&str], text: &str, at: u64) -> Post {
+        let mut post = Post::with_author("alice");
+        post.set_title(title);
+        for tag in tags {
+            post.add_tag(tag);
+        }
+        post.add_text(text);
+        post.request_review();
+        post.schedule(at);
+        post.approve_by(&Reviewer::new("bob")).unwrap();
+        post.tick(at);
+        post
+    }
+
+    #[test]
+    fn site_generation() {
+        let posts = vec![
+            (1, published_at("Older", &["Rust"], "# Old\n", 86_400)),
+            (2, Post::with_author("alice")),
+            (
+                3,
+                published_at("Newer <3", &["rust", "food"], "*new*", 172_800),
+            ),
+        ];
+        let config = site::SiteConfig {
+            title: String::from("My blog"),
+            base_url: String::from("https://example.com/"),
+        };
+        let pages = site::generate(&posts, &site::Templates::default(), &config);
+
+        let paths: Vec<String> = pages.iter().map(|p| p.path.display().to_string()).collect();
+        assert_eq!(
+            vec![
+                "posts/newer-3.html",
+                "posts/older.html",
+                "index.html",
+                "tags/food.html",
+                "tags/rust.html",
+                "feed.xml"
+            ],
+            paths
+        );
+
+        let content = |path: &str| {
+            &pages
+                .iter()
+                .find(|p| p.path == std::path::Path::new(path))
+                .unwrap()
+                .content
+        };
+
+        let post = content("posts/newer-3.html");
+        assert!(post.contains("<title>Newer &lt;3</title>"));
+        assert!(post.contains("<p class=\"meta\">1970-01-03 by alice</p>"));
+        assert!(post.contains("<a href=\"../tags/food.html\">food</a>"));
+        assert!(post.contains("<p><em>new</em></p>"));
+        assert!(post.contains("href=\"../index.html\""));
+
+        // 首页从新到旧
+        let index = content("index.html");
+        let newer = index.find("posts/newer-3.html").unwrap();
+        let older = index.find("posts/older.html").unwrap();
+        assert!(newer < older);
+
+        // 标签不区分大小写，使用最新一篇文章中的写法
+        let rust = content("tags/rust.html");
+        assert!(rust.contains("<h1>Tagged rust</h1>"));
+        assert!(rust.contains("../posts/newer-3.html"));
+        assert!(rust.contains("../posts/older.html"));
+
+        let feed = content("feed.xml");
+        assert!(feed.contains("<updated>1970-01-03T00:00:00Z</updated>"));
+        assert!(feed.contains("<id>https://example.com/posts/older.html</id>"));
+        assert!(feed.contains("&lt;h1&gt;Old&lt;/h1&gt;"));
+        assert_eq!(2, feed.matches("<entry>").count());
+    }
+
+    #[test]
+    fn site_slugs_are_unique_and_stay_inside_the_output() {
+        let posts = vec![
+            (1, published_at("Hello", &["C"], "one", 300)),
+            (2, published_at("Hello", &[], "two", 200)),
+            (3, published_at("Post 2", &["C++"], "three", 100)),
+        ];
+        let config = site::SiteConfig {
+            title: String::from("My blog"),
+            base_url: String::from("https://example.com/"),
+        };
+        let pages = site::generate(&posts, &site::Templates::default(), &config);
+        let content = |path: &str| {
+            &pages
+                .iter()
+                .find(|p| p.path == std::path::Path::new(path))
+                .unwrap_or_else(|| panic!("no page {}", path))
+                .content
+        };
+
+        // 重复的slug使用 post-<id>，post-2 已被第三篇文章使用
+        assert!(content("posts/hello.html").contains("one"));
+        assert!(content("posts/post-2-2.html").contains("two"));
+        assert!(content("posts/post-2.html").contains("three"));
+
+        // C 和 C++ 各有一页，显示原来的写法
+        assert!(content("tags/c.html").contains("<h1>Tagged C</h1>"));
+        assert!(content("tags/c-2.html").contains("<h1>Tagged C++</h1>"));
+        assert!(content("posts/post-2.html").contains("<a href=\"../tags/c-2.html\">C++</a>"));
+
+        let escaping = [site::Page {
+            path: std::path::PathBuf::from("../x.html"),
+            content: String::new(),
+        }];
+        let dir = std::env::temp_dir().join(format!("blog-escape-{}", std::process::id()));
+        let err = site::write(dir.join("out"), &escaping).unwrap_err();
+        assert_eq!(std::io::ErrorKind::InvalidInput, err.kind());
+        assert!(!dir.join("x.html").exists());
+
+        // 仓库中被改坏的slug在读取时就会被拒绝
+        let text = store::encode(&posts[0].1).replace("\"hello\"", "\"../../x\"");
+        assert!(matches!(
+            store::decode(&text),
+            Err(StoreError::Corrupt(msg)) if msg == "invalid slug \"../../x\""
+        ));
+    }
+
+    #[test]
+    fn cli_build_uses_templates_on_disk() {
+        let dir = std::env::temp_dir().join(format!("blog-site-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let templates = dir.join("templates");
+        site::Templates::write_defaults(&templates).unwrap();
+        std::fs::write(templates.join("item.html"), "* {{title}}\n").unwrap();
+
+        let mut repo = MemoryRepository::new();
+        repo.insert(&published_at("Hello", &[], "hi", 0)).unwrap();
+        let clock = Arc::new(ManualClock::new(0));
+        let out = dir.join("out");
+        let line = format!(
+            "blog --store {} build {} --title Mine",
+            dir.display(),
+            out.display()
+        );
+        assert_eq!(
+            Ok(format!("wrote 3 files to {}\n", out.display())),
+            cli::execute(&mut repo, &command(&line), &clock).map_err(|e| e.to_string())
+        );
+
+        let index = std::fs::read_to_string(out.join("index.html")).unwrap();
+        assert!(index.contains("<title>Mine</title>"));
+        assert!(index.contains("* Hello\n"));
+        assert!(out.join("posts/hello.html").exists());
+        assert!(std::fs::read_to_string(out.join("feed.xml"))
+            .unwrap()
+            .contains("<id>http://localhost/</id>"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
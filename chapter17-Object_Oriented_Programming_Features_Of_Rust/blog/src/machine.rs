@@ -0,0 +1,230 @@
+// 通用的状态机：状态和事件都是简单的值，转换规则集中写在一张表里。
+// Post 的审阅流程就建立在它之上，发票、工单等其他流程也可以复用。
+//
+// 同一个 (状态, 事件) 可以有多条规则，按添加的顺序检查守卫条件，
+// 第一条守卫通过的规则生效；没有守卫的规则总是通过。
+
+use std::error::Error;
+use std::fmt;
+
+// 守卫条件，根据上下文C决定一条规则能否生效
+pub type Guard<C> = fn(&C) -> bool;
+
+struct Transition<S, E, C> {
+    from: S,
+    event: E,
+    to: S,
+    guard: Option<Guard<C>>,
+}
+
+pub struct StateMachine<S, E, C> {
+    states: Vec<S>,
+    initial: S,
+    transitions: Vec<Transition<S, E, C>>,
+}
+
+// 构建时发现的规则表错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DefinitionError<S, E> {
+    // 初始状态或规则用到了没有声明的状态
+    UnknownState(S),
+    DuplicateState(S),
+    // 没有守卫的规则之后又有同一 (状态, 事件) 的规则，后者永远不会生效
+    Shadowed { from: S, event: E },
+    // 从初始状态出发无法到达的状态
+    Unreachable(S),
+}
+
+impl<S: fmt::Debug, E: fmt::Debug> fmt::Display for DefinitionError<S, E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DefinitionError::UnknownState(s) => write!(f, "state {:?} is not declared", s),
+            DefinitionError::DuplicateState(s) => write!(f, "state {:?} is declared twice", s),
+            DefinitionError::Shadowed { from, event } => write!(
+                f,
+                "transition on {:?} from {:?} can never fire: an earlier one has no guard",
+                event, from
+            ),
+            DefinitionError::Unreachable(s) => {
+                write!(f, "state {:?} cannot be reached from the initial state", s)
+            }
+        }
+    }
+}
+
+impl<S: fmt::Debug, E: fmt::Debug> Error for DefinitionError<S, E> {}
+
+// 触发事件失败的原因，状态保持不变
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FireError<S, E> {
+    // 该状态没有处理这个事件的规则
+    NoTransition { state: S, event: E },
+    // 有规则，但守卫条件都没有通过
+    GuardRejected { state: S, event: E },
+}
+
+impl<S: fmt::Debug, E: fmt::Debug> fmt::Display for FireError<S, E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FireError::NoTransition { state, event } => {
+                write!(f, "no transition on {:?} from {:?}", event, state)
+            }
+            FireError::GuardRejected { state, event } => {
+                write!(
+                    f,
+                    "transition on {:?} from {:?} was not allowed",
+                    event, state
+                )
+            }
+        }
+    }
+}
+
+impl<S: fmt::Debug, E: fmt::Debug> Error for FireError<S, E> {}
+
+pub struct Builder<S, E, C> {
+    states: Vec<S>,
+    initial: S,
+    transitions: Vec<Transition<S, E, C>>,
+}
+
+impl<S, E, C> Builder<S, E, C>
+where
+    S: Copy + Eq + fmt::Debug,
+    E: Copy + Eq + fmt::Debug,
+{
+    pub fn new(initial: S) -> Builder<S, E, C> {
+        Builder {
+            states: Vec::new(),
+            initial,
+            transitions: Vec::new(),
+        }
+    }
+
+    pub fn state(mut self, state: S) -> Self {
+        self.states.push(state);
+        self
+    }
+
+    // 无条件的规则
+    pub fn transition(mut self, from: S, event: E, to: S) -> Self {
+        self.transitions.push(Transition {
+            from,
+            event,
+            to,
+            guard: None,
+        });
+        self
+    }
+
+    // 只有guard返回true时才生效的规则
+    pub fn guarded(mut self, from: S, event: E, to: S, guard: Guard<C>) -> Self {
+        self.transitions.push(Transition {
+            from,
+            event,
+            to,
+            guard: Some(guard),
+        });
+        self
+    }
+
+    // 检查规则表，返回第一个发现的错误
+    pub fn build(self) -> Result<StateMachine<S, E, C>, DefinitionError<S, E>> {
+        for (i, &state) in self.states.iter().enumerate() {
+            if self.states[..i].contains(&state) {
+                return Err(DefinitionError::DuplicateState(state));
+            }
+        }
+
+        let declared = |s: S| {
+            if self.states.contains(&s) {
+                Ok(())
+            } else {
+                Err(DefinitionError::UnknownState(s))
+            }
+        };
+        declared(self.initial)?;
+        for t in &self.transitions {
+            declared(t.from)?;
+            declared(t.to)?;
+        }
+
+        for (i, t) in self.transitions.iter().enumerate() {
+            let shadowed = t.guard.is_none()
+                && self.transitions[i + 1..]
+                    .iter()
+                    .any(|later| later.from == t.from && later.event == t.event);
+            if shadowed {
+                return Err(DefinitionError::Shadowed {
+                    from: t.from,
+                    event: t.event,
+                });
+            }
+        }
+
+        // 忽略守卫条件，从初始状态出发做一次广度优先搜索
+        let mut reached = vec![self.initial];
+        let mut next = 0;
+        while next < reached.len() {
+            let from = reached[next];
+            next += 1;
+            for t in self.transitions.iter().filter(|t| t.from == from) {
+                if !reached.contains(&t.to) {
+                    reached.push(t.to);
+                }
+            }
+        }
+        if let Some(&state) = self.states.iter().find(|s| !reached.contains(s)) {
+            return Err(DefinitionError::Unreachable(state));
+        }
+
+        Ok(StateMachine {
+            states: self.states,
+            initial: self.initial,
+            transitions: self.transitions,
+        })
+    }
+}
+
+impl<S, E, C> StateMachine<S, E, C>
+where
+    S: Copy + Eq + fmt::Debug,
+    E: Copy + Eq + fmt::Debug,
+{
+    pub fn builder(initial: S) -> Builder<S, E, C> {
+        Builder::new(initial)
+    }
+
+    pub fn initial(&self) -> S {
+        self.initial
+    }
+
+    pub fn states(&self) -> &[S] {
+        &self.states
+    }
+
+    // state是否有处理event的规则，不考虑守卫条件
+    pub fn accepts(&self, state: S, event: E) -> bool {
+        self.transitions
+            .iter()
+            .any(|t| t.from == state && t.event == event)
+    }
+
+    // state收到event后应该转换到的状态
+    pub fn next(&self, state: S, event: E, context: &C) -> Result<S, FireError<S, E>> {
+        let mut candidates = self
+            .transitions
+            .iter()
+            .filter(|t| t.from == state && t.event == event)
+            .peekable();
+
+        if candidates.peek().is_none() {
+            return Err(FireError::NoTransition { state, event });
+        }
+
+        candidates
+            .find(|t| t.guard.is_none_or(|guard| guard(context)))
+            .map(|t| t.to)
+            .ok_or(FireError::GuardRejected { state, event })
+    }
+}
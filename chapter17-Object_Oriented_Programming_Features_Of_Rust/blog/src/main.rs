@@ -1,14 +1,18 @@
-use blog::Post;
+use std::env;
+use std::process;
 
-fn main() {
-    let mut post = Post::new();
+use blog::cli::{self, Config};
 
-    post.add_text("l ate an egg");
-    assert_eq!("", post.content());
+fn main() {
+    let args: Vec<String> = env::args().collect();
 
-    post.request_review();
-    assert_eq!("", post.content());
+    let config = Config::new(&args).unwrap_or_else(|err| {
+        eprintln!("{}\n\n{}", err, cli::USAGE);
+        process::exit(2)
+    });
 
-    post.approve();
-    assert_eq!("l ate an egg", post.content());
-}
\ No newline at end of file
+    if let Err(e) = cli::run(config) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,263 @@
+// 把文章内容从Markdown渲染为HTML。
+// 只支持常用的子集：标题、段落、强调、行内代码、代码块、列表和链接，
+// 其余文本一律转义后原样输出。
+
+// 渲染一段Markdown文本
+pub fn to_html(text: &str) -> String {
+    let mut out = String::new();
+    let mut lines = text.lines().peekable();
+
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim_start();
+
+        if trimmed.is_empty() {
+            continue;
+        }
+
+        // 围栏代码块：``` 到下一个 ``` 之间的内容原样输出
+        if let Some(lang) = trimmed.strip_prefix("```") {
+            let lang = lang.trim();
+            if lang.is_empty() {
+                out.push_str("<pre><code>");
+            } else {
+                out.push_str(&format!("<pre><code class=\"language-{}\">", escape(lang)));
+            }
+            for line in lines.by_ref() {
+                if line.trim_start().starts_with("```") {
+                    break;
+                }
+                out.push_str(&escape(line));
+                out.push('\n');
+            }
+            out.push_str("</code></pre>\n");
+            continue;
+        }
+
+        if let Some((level, title)) = heading(trimmed) {
+            out.push_str(&format!("<h{0}>{1}</h{0}>\n", level, inline(title)));
+            continue;
+        }
+
+        // 连续的同类列表项组成一个列表
+        if let Some((ordered, first)) = list_item(trimmed) {
+            let mut items = vec![first];
+            while let Some(line) = lines.peek() {
+                match list_item(line.trim_start()) {
+                    Some((o, item)) if o == ordered => {
+                        items.push(item);
+                        lines.next();
+                    }
+                    _ => break,
+                }
+            }
+
+            let tag = if ordered { "ol" } else { "ul" };
+            out.push_str(&format!("<{}>\n", tag));
+            for item in items {
+                out.push_str(&format!("<li>{}</li>\n", inline(item)));
+            }
+            out.push_str(&format!("</{}>\n", tag));
+            continue;
+        }
+
+        // 段落：连续的非空行合并为一段，遇到空行或其他块结束
+        let mut paragraph = vec![trimmed];
+        while let Some(line) = lines.peek() {
+            let next = line.trim_start();
+            if next.is_empty()
+                || next.starts_with("```")
+                || heading(next).is_some()
+                || list_item(next).is_some()
+            {
+                break;
+            }
+            paragraph.push(next);
+            lines.next();
+        }
+        out.push_str(&format!("<p>{}</p>\n", inline(&paragraph.join("\n"))));
+    }
+
+    out
+}
+
+// "## 标题" 返回 (2, "标题")
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    if level == 0 || level > 6 {
+        return None;
+    }
+    let rest = &line[level..];
+    if rest.is_empty() {
+        Some((level, ""))
+    } else {
+        rest.strip_prefix(' ').map(|title| (level, title.trim()))
+    }
+}
+
+// 列表项，返回 (是否为有序列表, 内容)
+fn list_item(line: &str) -> Option<(bool, &str)> {
+    for marker in ["- ", "* ", "+ "] {
+        if let Some(item) = line.strip_prefix(marker) {
+            return Some((false, item.trim()));
+        }
+    }
+
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 {
+        if let Some(item) = line[digits..].strip_prefix(". ") {
+            return Some((true, item.trim()));
+        }
+    }
+    None
+}
+
+// 行内元素：`代码`、**加粗**、*强调*/_强调_ 和 [文字](地址)
+fn inline(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+
+    while let Some(c) = rest.chars().next() {
+        // _ 的前面是字母、数字或 _ 时不是强调，如 snake_case_name
+        let before = text[..text.len() - rest.len()].chars().next_back();
+        let boundary = c != '_' || !before.is_some_and(|b| b == '_' || b.is_alphanumeric());
+
+        if c == '\\' {
+            // 反斜杠转义下一个标点字符
+            if let Some(next) = rest[1..]
+                .chars()
+                .next()
+                .filter(|c| c.is_ascii_punctuation())
+            {
+                out.push_str(&escape(&next.to_string()));
+                rest = &rest[1 + next.len_utf8()..];
+                continue;
+            }
+        } else if c == '`' {
+            if let Some(end) = rest[1..].find('`') {
+                out.push_str("<code>");
+                out.push_str(&escape(&rest[1..1 + end]));
+                out.push_str("</code>");
+                rest = &rest[end + 2..];
+                continue;
+            }
+        } else if (rest.starts_with("**") || rest.starts_with("__"))
+            && boundary
+            && opens(&rest[2..])
+        {
+            let marker = &rest[..2];
+            if let Some(end) = closing(&rest[2..], marker) {
+                out.push_str("<strong>");
+                out.push_str(&inline(&rest[2..2 + end]));
+                out.push_str("</strong>");
+                rest = &rest[end + 4..];
+                continue;
+            }
+        } else if (c == '*' || c == '_') && boundary && opens(&rest[1..]) {
+            if let Some(end) = closing(&rest[1..], &rest[..1]) {
+                out.push_str("<em>");
+                out.push_str(&inline(&rest[1..1 + end]));
+                out.push_str("</em>");
+                rest = &rest[end + 2..];
+                continue;
+            }
+        } else if c == '[' {
+            if let Some((label, href, len)) = link(rest) {
+                // 地址的协议不安全时只保留文字
+                if safe_href(href) {
+                    out.push_str(&format!(
+                        "<a href=\"{}\">{}</a>",
+                        escape(href),
+                        inline(label)
+                    ));
+                } else {
+                    out.push_str(&inline(label));
+                }
+                rest = &rest[len..];
+                continue;
+            }
+        }
+
+        out.push_str(&escape(&rest[..c.len_utf8()]));
+        rest = &rest[c.len_utf8()..];
+    }
+
+    out
+}
+
+// 紧跟空白的 * 或 _ 只是普通字符，如 "2 * 3"
+fn opens(after: &str) -> bool {
+    after.chars().next().is_some_and(|c| !c.is_whitespace())
+}
+
+// 结束标记在body中的位置，强调的内容不能为空。
+// _ 和 __ 的后面是字母或数字时不是结束标记
+fn closing(body: &str, marker: &str) -> Option<usize> {
+    let end = if marker.starts_with('_') {
+        body.match_indices(marker).map(|(i, _)| i).find(|&i| {
+            !body[i + marker.len()..]
+                .chars()
+                .next()
+                .is_some_and(char::is_alphanumeric)
+        })?
+    } else {
+        body.find(marker)?
+    };
+    Some(end).filter(|&end| end > 0)
+}
+
+// "[文字](地址)"，返回文字、地址以及整个链接的字节长度。
+// 文字到与开头的 [ 配对的 ] 为止，] 后面必须紧跟 (，否则不是链接
+fn link(text: &str) -> Option<(&str, &str, usize)> {
+    let mut depth = 0;
+    let mut close = None;
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    close = Some(i);
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let close = close?;
+    let href = text[close + 1..].strip_prefix('(')?;
+    let end = href.find(')')?;
+    Some((&text[1..close], href[..end].trim(), close + end + 3))
+}
+
+// 只允许 http、https、mailto 和相对地址，javascript: 之类的协议不生成链接。
+// 第一个 '/'、'?' 或 '#' 之前没有 ':' 的地址是相对地址。
+fn safe_href(href: &str) -> bool {
+    let end = href.find(['/', '?', '#']).unwrap_or(href.len());
+    match href[..end].split_once(':') {
+        Some((scheme, _)) => ["http", "https", "mailto"]
+            .iter()
+            .any(|allowed| scheme.eq_ignore_ascii_case(allowed)),
+        None => true,
+    }
+}
+
+// 转义HTML中有特殊含义的字符
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,68 @@
+// 文章的元数据：标题、标签、slug以及创建和修改时间
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metadata {
+    pub title: String,
+    // 按添加顺序保存，不重复
+    pub tags: Vec<String>,
+    // 由标题生成，用作文章的地址；在Collection中唯一
+    pub slug: String,
+    pub created: u64,
+    pub updated: u64,
+    // 最近一次发布的时间，从未发布时为None
+    pub published: Option<u64>,
+}
+
+impl Metadata {
+    pub fn new(now: u64) -> Metadata {
+        Metadata {
+            created: now,
+            updated: now,
+            ..Metadata::default()
+        }
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+    }
+}
+
+// 把标题转换为只含小写字母、数字和连字符的slug，
+// 如 "Hello, World!" 转换为 "hello-world"
+pub fn slugify(title: &str) -> String {
+    let mut slug = String::new();
+    for word in title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+    {
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+        slug.extend(word.chars().flat_map(char::to_lowercase));
+    }
+
+    if slug.is_empty() {
+        String::from("post")
+    } else {
+        slug
+    }
+}
+
+// slug用作生成网站时的文件名，只能由小写字母、数字和 - 组成。
+// 空字符串表示文章还没有标题
+pub fn is_valid_slug(slug: &str) -> bool {
+    slug.chars()
+        .all(|c| c == '-' || (c.is_alphanumeric() && !c.is_uppercase()))
+}
+
+// 生成一个taken返回false的slug，重复时依次追加 -2、-3 ……
+pub fn unique_slug<F: Fn(&str) -> bool>(title: &str, taken: F) -> String {
+    slug_candidates(&slugify(title))
+        .find(|slug| !taken(slug))
+        .unwrap()
+}
+
+// slug本身以及依次追加 -2、-3 …… 的候选slug
+pub fn slug_candidates(slug: &str) -> impl Iterator<Item = String> + '_ {
+    std::iter::once(slug.to_string()).chain((2..).map(move |n| format!("{}-{}", slug, n)))
+}
@@ -0,0 +1,111 @@
+use std::error::Error;
+use std::fmt;
+
+// 审阅文章的人，以及他拥有的角色（如 "editor"、"legal"）
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reviewer {
+    pub name: String,
+    pub roles: Vec<String>,
+}
+
+impl Reviewer {
+    pub fn new(name: &str) -> Reviewer {
+        Reviewer {
+            name: name.to_string(),
+            roles: Vec::new(),
+        }
+    }
+
+    pub fn with_role(mut self, role: &str) -> Reviewer {
+        self.roles.push(role.to_string());
+        self
+    }
+
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|r| r == role)
+    }
+}
+
+// 发布前需要满足的审批规则
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApprovalPolicy {
+    // 需要多少位不同的审阅者批准
+    pub required_approvals: usize,
+    // 每个角色都至少要有一位拥有该角色的审阅者批准
+    pub required_roles: Vec<String>,
+    // 是否允许作者批准自己的文章
+    pub allow_self_approval: bool,
+}
+
+impl Default for ApprovalPolicy {
+    // 默认规则与最初的行为一致：一次批准即可发布
+    fn default() -> Self {
+        ApprovalPolicy {
+            required_approvals: 1,
+            required_roles: Vec::new(),
+            allow_self_approval: false,
+        }
+    }
+}
+
+impl ApprovalPolicy {
+    pub fn approvals(required_approvals: usize) -> ApprovalPolicy {
+        ApprovalPolicy {
+            required_approvals,
+            ..ApprovalPolicy::default()
+        }
+    }
+
+    pub fn with_role(mut self, role: &str) -> ApprovalPolicy {
+        self.required_roles.push(role.to_string());
+        self
+    }
+
+    pub fn allow_self_approval(mut self) -> ApprovalPolicy {
+        self.allow_self_approval = true;
+        self
+    }
+
+    // 已有的批准是否满足规则
+    pub fn is_satisfied(&self, approvals: &[Reviewer]) -> bool {
+        approvals.len() >= self.required_approvals
+            && self
+                .required_roles
+                .iter()
+                .all(|role| approvals.iter().any(|r| r.has_role(role)))
+    }
+}
+
+// approve_by 被拒绝的原因
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApprovalError {
+    // 文章不在等待审阅的状态
+    NotPendingReview,
+    // 作者不能批准自己的文章
+    SelfApproval(String),
+    // 同一位审阅者只能批准一次
+    AlreadyApproved(String),
+    // 还有这么多讨论串没有解决
+    UnresolvedComments(usize),
+    // 这次批准之后仍不满足审批规则，只由 try_approve 返回
+    PolicyNotMet,
+}
+
+impl fmt::Display for ApprovalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ApprovalError::NotPendingReview => write!(f, "post is not pending review"),
+            ApprovalError::SelfApproval(name) => {
+                write!(f, "{} cannot approve their own post", name)
+            }
+            ApprovalError::AlreadyApproved(name) => {
+                write!(f, "{} has already approved this post", name)
+            }
+            ApprovalError::UnresolvedComments(1) => write!(f, "1 comment is unresolved"),
+            ApprovalError::UnresolvedComments(n) => write!(f, "{} comments are unresolved", n),
+            ApprovalError::PolicyNotMet => write!(f, "the approval policy is not met"),
+        }
+    }
+}
+
+impl Error for ApprovalError {}
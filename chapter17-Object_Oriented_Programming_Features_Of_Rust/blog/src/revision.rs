@@ -0,0 +1,118 @@
+use std::fmt;
+
+// 文章内容的一个版本
+#[derive(Debug, Clone, PartialEq)]
+pub struct Revision {
+    // 从 1 开始编号
+    pub number: usize,
+    pub author: String,
+    // 自 UNIX 纪元以来的秒数
+    pub timestamp: u64,
+    // 本次编辑后的完整内容
+    pub content: String,
+}
+
+// 按时间顺序保存的修订记录
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct History {
+    revisions: Vec<Revision>,
+}
+
+impl History {
+    pub fn new() -> History {
+        History::default()
+    }
+
+    // 记录一次编辑，返回新的修订号
+    pub fn record(&mut self, author: &str, timestamp: u64, content: &str) -> usize {
+        let number = self.revisions.len() + 1;
+        self.revisions.push(Revision {
+            number,
+            author: author.to_string(),
+            timestamp,
+            content: content.to_string(),
+        });
+        number
+    }
+
+    pub fn revisions(&self) -> &[Revision] {
+        &self.revisions
+    }
+
+    pub fn get(&self, number: usize) -> Option<&Revision> {
+        self.revisions.get(number.checked_sub(1)?)
+    }
+
+    pub fn latest(&self) -> Option<&Revision> {
+        self.revisions.last()
+    }
+
+    // 两个修订版本之间按行比较的差异，任一修订号不存在时返回None
+    pub fn diff(&self, from: usize, to: usize) -> Option<Vec<DiffLine>> {
+        Some(diff(&self.get(from)?.content, &self.get(to)?.content))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffLine {
+    Same(String),
+    Added(String),
+    Removed(String),
+}
+
+// 以 diff -u 的风格显示：未变的行以空格开头，新增的行以+开头，删除的行以-开头
+impl fmt::Display for DiffLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DiffLine::Same(line) => write!(f, " {}", line),
+            DiffLine::Added(line) => write!(f, "+{}", line),
+            DiffLine::Removed(line) => write!(f, "-{}", line),
+        }
+    }
+}
+
+// 基于最长公共子序列的按行差异
+pub fn diff(old: &str, new: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // lcs[i][j] 为 old[i..] 与 new[j..] 的最长公共子序列长度
+    let mut lcs = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            lines.push(DiffLine::Same(old[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            lines.push(DiffLine::Removed(old[i].to_string()));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(new[j].to_string()));
+            j += 1;
+        }
+    }
+    lines.extend(
+        old[i..]
+            .iter()
+            .map(|line| DiffLine::Removed(line.to_string())),
+    );
+    lines.extend(
+        new[j..]
+            .iter()
+            .map(|line| DiffLine::Added(line.to_string())),
+    );
+
+    lines
+}
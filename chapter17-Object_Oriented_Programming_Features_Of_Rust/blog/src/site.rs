@@ -0,0 +1,422 @@
+// 静态网站生成：把已发布的文章渲染为HTML页面、按日期排列的首页、
+// 每个标签一页以及Atom订阅源。
+//
+// 页面由模板目录中的文件生成，模板中的 {{name}} 会被替换为对应的值；
+// 目录中没有的模板使用内置的默认模板。
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::markdown::escape;
+use crate::metadata;
+use crate::{Post, PostId};
+
+// 模板文件名以及默认内容
+const DEFAULTS: &[(&str, &str)] = &[
+    (
+        "layout.html",
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>{{title}}</title>
+<link rel=\"alternate\" type=\"application/atom+xml\" href=\"{{root}}feed.xml\">
+</head>
+<body>
+<nav><a href=\"{{root}}index.html\">Home</a></nav>
+{{body}}
+</body>
+</html>
+",
+    ),
+    (
+        "post.html",
+        "<article>
+<h1>{{title}}</h1>
+<p class=\"meta\">{{date}} by {{author}}</p>
+<ul class=\"tags\">{{tags}}</ul>
+{{content}}</article>
+",
+    ),
+    ("index.html", "<h1>Posts</h1>\n<ul>\n{{items}}</ul>\n"),
+    (
+        "tag.html",
+        "<h1>Tagged {{tag}}</h1>\n<ul>\n{{items}}</ul>\n",
+    ),
+    (
+        "item.html",
+        "<li><a href=\"{{url}}\">{{title}}</a> <time>{{date}}</time></li>\n",
+    ),
+    (
+        "feed.xml",
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>
+<feed xmlns=\"http://www.w3.org/2005/Atom\">
+<title>{{title}}</title>
+<id>{{base}}</id>
+<link href=\"{{base}}\"/>
+<link rel=\"self\" href=\"{{base}}feed.xml\"/>
+<updated>{{updated}}</updated>
+{{entries}}</feed>
+",
+    ),
+    (
+        "entry.xml",
+        "<entry>
+<title>{{title}}</title>
+<id>{{url}}</id>
+<link href=\"{{url}}\"/>
+<author><name>{{author}}</name></author>
+<published>{{published}}</published>
+<updated>{{updated}}</updated>
+<content type=\"html\">{{content}}</content>
+</entry>
+",
+    ),
+];
+
+pub struct Templates {
+    templates: BTreeMap<String, String>,
+}
+
+impl Default for Templates {
+    fn default() -> Self {
+        Templates {
+            templates: DEFAULTS
+                .iter()
+                .map(|(name, text)| (name.to_string(), text.to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl Templates {
+    // 读取目录中的模板，目录或某个模板文件不存在时使用默认模板
+    pub fn load<P: AsRef<Path>>(dir: P) -> io::Result<Templates> {
+        let mut templates = Templates::default();
+        for (name, _) in DEFAULTS {
+            match fs::read_to_string(dir.as_ref().join(name)) {
+                Ok(text) => {
+                    templates.templates.insert(name.to_string(), text);
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(templates)
+    }
+
+    // 把默认模板写入目录，便于在此基础上修改
+    pub fn write_defaults<P: AsRef<Path>>(dir: P) -> io::Result<()> {
+        fs::create_dir_all(dir.as_ref())?;
+        for (name, text) in DEFAULTS {
+            fs::write(dir.as_ref().join(name), text)?;
+        }
+        Ok(())
+    }
+
+    fn render(&self, name: &str, vars: &[(&str, &str)]) -> String {
+        render(&self.templates[name], vars)
+    }
+}
+
+// 替换模板中的 {{name}}。替换进去的值不会再被展开，没有提供的名称保持原样
+pub fn render(template: &str, vars: &[(&str, &str)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let value = after.find("}}").and_then(|end| {
+            let name = after[..end].trim();
+            vars.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| (*value, end))
+        });
+
+        match value {
+            Some((value, end)) => {
+                out.push_str(value);
+                rest = &after[end + 2..];
+            }
+            None => {
+                out.push_str("{{");
+                rest = after;
+            }
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
+
+pub struct SiteConfig {
+    // 网站标题，用于首页和订阅源
+    pub title: String,
+    // 网站的绝对地址，以 / 结尾，订阅源中的链接以它为前缀
+    pub base_url: String,
+}
+
+// 一个要写入的文件，path相对于输出目录
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page {
+    pub path: PathBuf,
+    pub content: String,
+}
+
+// 一篇要发布的文章以及它在网站中的文件名
+struct Entry<'a> {
+    post: &'a Post,
+    slug: String,
+    title: String,
+    date: u64,
+}
+
+// 生成网站的所有文件，只包含已发布的文章。
+// 首页、标签页和订阅源中的文章按发布时间从新到旧排列。
+pub fn generate(posts: &[(PostId, Post)], templates: &Templates, config: &SiteConfig) -> Vec<Page> {
+    let published: Vec<(PostId, &Post)> = posts
+        .iter()
+        .filter(|(_, post)| post.state_name() == "published")
+        .map(|(id, post)| (*id, post))
+        .collect();
+
+    // 先收集所有文章自己的slug，重复时只有第一篇使用它；
+    // 其余文章使用 post-<id>，与已有的slug重复时再追加 -2、-3 ……
+    let mut used = BTreeSet::new();
+    let own: Vec<bool> = published
+        .iter()
+        .map(|(_, post)| !post.slug().is_empty() && used.insert(post.slug().to_string()))
+        .collect();
+
+    let mut entries: Vec<Entry> = Vec::new();
+    for ((id, post), own) in published.into_iter().zip(own) {
+        let slug = if own {
+            post.slug().to_string()
+        } else {
+            let slug = metadata::unique_slug(&format!("post-{}", id), |s| used.contains(s));
+            used.insert(slug.clone());
+            slug
+        };
+        let title = if post.title().is_empty() {
+            post.content().lines().next().unwrap_or("").to_string()
+        } else {
+            post.title().to_string()
+        };
+        entries.push(Entry {
+            post,
+            slug,
+            title,
+            date: post.published().unwrap_or(post.created()),
+        });
+    }
+    entries.sort_by(|a, b| b.date.cmp(&a.date).then_with(|| a.slug.cmp(&b.slug)));
+
+    // 标签不区分大小写，显示最新一篇文章中的写法。
+    // 不同的标签（如 C 和 C++）slugify 后相同时，后面的标签追加 -2、-3 ……
+    let mut tags: BTreeMap<String, (String, Vec<&Entry>)> = BTreeMap::new();
+    for entry in &entries {
+        for tag in entry.post.tags() {
+            tags.entry(tag.to_lowercase())
+                .or_insert_with(|| (tag.clone(), Vec::new()))
+                .1
+                .push(entry);
+        }
+    }
+    let mut tag_slugs: BTreeMap<&str, String> = BTreeMap::new();
+    for (key, (tag, _)) in &tags {
+        let slug = metadata::unique_slug(tag, |s| tag_slugs.values().any(|used| used == s));
+        tag_slugs.insert(key, slug);
+    }
+
+    let mut pages = Vec::new();
+
+    for entry in &entries {
+        let tags: String = entry
+            .post
+            .tags()
+            .iter()
+            .map(|tag| {
+                format!(
+                    "<li><a href=\"../tags/{}.html\">{}</a></li>",
+                    tag_slugs[tag.to_lowercase().as_str()],
+                    escape(tag)
+                )
+            })
+            .collect();
+        let body = templates.render(
+            "post.html",
+            &[
+                ("title", &escape(&entry.title)),
+                ("author", &escape(entry.post.author())),
+                ("date", &date(entry.date)),
+                ("tags", &tags),
+                ("content", &entry.post.render_html()),
+            ],
+        );
+        pages.push(page(
+            templates,
+            format!("posts/{}.html", entry.slug),
+            &entry.title,
+            "../",
+            &body,
+        ));
+    }
+
+    let all: Vec<&Entry> = entries.iter().collect();
+    let index = templates.render("index.html", &[("items", &items(templates, &all, ""))]);
+    pages.push(page(
+        templates,
+        String::from("index.html"),
+        &config.title,
+        "",
+        &index,
+    ));
+
+    for (key, (tag, tagged)) in &tags {
+        let body = templates.render(
+            "tag.html",
+            &[
+                ("tag", &escape(tag)),
+                ("items", &items(templates, tagged, "../")),
+            ],
+        );
+        pages.push(page(
+            templates,
+            format!("tags/{}.html", tag_slugs[key.as_str()]),
+            tag,
+            "../",
+            &body,
+        ));
+    }
+
+    pages.push(Page {
+        path: PathBuf::from("feed.xml"),
+        content: feed(templates, &entries, config),
+    });
+
+    pages
+}
+
+// 把生成的文件写入out_dir，返回写入的文件数。
+// 绝对路径或包含 .. 的路径会写到out_dir之外，不写入任何文件并返回错误
+pub fn write<P: AsRef<Path>>(out_dir: P, pages: &[Page]) -> io::Result<usize> {
+    if let Some(page) = pages.iter().find(|page| {
+        page.path
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_)))
+    }) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "page path {} is outside the output directory",
+                page.path.display()
+            ),
+        ));
+    }
+
+    for page in pages {
+        let path = out_dir.as_ref().join(&page.path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, &page.content)?;
+    }
+    Ok(pages.len())
+}
+
+// 套上layout的HTML页面。root为从该页面回到网站根目录的相对路径
+fn page(templates: &Templates, path: String, title: &str, root: &str, body: &str) -> Page {
+    Page {
+        path: PathBuf::from(path),
+        content: templates.render(
+            "layout.html",
+            &[("title", &escape(title)), ("root", root), ("body", body)],
+        ),
+    }
+}
+
+fn items(templates: &Templates, entries: &[&Entry], root: &str) -> String {
+    entries
+        .iter()
+        .map(|entry| {
+            templates.render(
+                "item.html",
+                &[
+                    ("url", &format!("{}posts/{}.html", root, entry.slug)),
+                    ("title", &escape(&entry.title)),
+                    ("date", &date(entry.date)),
+                    ("author", &escape(entry.post.author())),
+                ],
+            )
+        })
+        .collect()
+}
+
+fn feed(templates: &Templates, entries: &[Entry], config: &SiteConfig) -> String {
+    let rendered: String = entries
+        .iter()
+        .map(|entry| {
+            templates.render(
+                "entry.xml",
+                &[
+                    (
+                        "url",
+                        &escape(&format!("{}posts/{}.html", config.base_url, entry.slug)),
+                    ),
+                    ("title", &escape(&entry.title)),
+                    ("author", &escape(entry.post.author())),
+                    ("published", &timestamp(entry.date)),
+                    ("updated", &timestamp(entry.post.updated().max(entry.date))),
+                    ("content", &escape(&entry.post.render_html())),
+                ],
+            )
+        })
+        .collect();
+
+    // 订阅源的更新时间为最新一篇文章的时间
+    let updated = entries.first().map_or(0, |e| e.date);
+    templates.render(
+        "feed.xml",
+        &[
+            ("title", &escape(&config.title)),
+            ("base", &escape(&config.base_url)),
+            ("updated", &timestamp(updated)),
+            ("entries", &rendered),
+        ],
+    )
+}
+
+// UNIX秒转换为UTC的 (年, 月, 日, 时, 分, 秒)
+fn civil(secs: u64) -> (u64, u64, u64, u64, u64, u64) {
+    let days = secs / 86_400;
+    let rem = secs % 86_400;
+
+    // 以0000-03-01为起点按400年周期计算，参见 Howard Hinnant 的 civil_from_days
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    (year, month, day, rem / 3600, rem % 3600 / 60, rem % 60)
+}
+
+// 2024-01-31
+pub fn date(secs: u64) -> String {
+    let (y, m, d, ..) = civil(secs);
+    format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+// RFC 3339 格式，Atom要求的时间格式，如 2024-01-31T08:00:00Z
+pub fn timestamp(secs: u64) -> String {
+    let (y, mo, d, h, mi, s) = civil(secs);
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", y, mo, d, h, mi, s)
+}
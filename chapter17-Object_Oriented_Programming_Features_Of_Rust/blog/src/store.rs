@@ -0,0 +1,851 @@
+// 文章的持久化：PostRepository 抽象了文章的存取，
+// MemoryRepository 用于测试，FileRepository 把每篇文章保存为目录下的一个JSON文件。
+//
+// 每篇文章有一个版本号，插入时为1，每次更新加1。多个编辑者同时修改同一篇文章时，
+// 先用 get_versioned 读出文章和版本号，再用 update_if 写回；
+// 如果期间有人写入过，update_if 返回 Conflict，调用者应重新读取后再修改。
+//
+// 仓库保证文章的slug互不相同：插入或更新时slug已被其他文章使用，就依次追加 -2、-3 ……
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::clock::{Clock, SystemClock};
+use crate::json::{self, Value};
+use crate::metadata;
+use crate::{
+    ApprovalPolicy, Comment, Comments, History, LineRange, Metadata, Post, PostParts, PostState,
+    Reviewer,
+};
+
+pub type PostId = u64;
+pub type Version = u64;
+
+#[derive(Debug)]
+pub enum StoreError {
+    NotFound(PostId),
+    Io(io::Error),
+    // 文件内容无法还原为文章
+    Corrupt(String),
+    // 读出文章之后有人更新过它，写入被拒绝
+    Conflict {
+        id: PostId,
+        expected: Version,
+        actual: Version,
+    },
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StoreError::NotFound(id) => write!(f, "post {} does not exist", id),
+            StoreError::Io(e) => write!(f, "storage error: {}", e),
+            StoreError::Corrupt(msg) => write!(f, "corrupt post data: {}", msg),
+            StoreError::Conflict {
+                id,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "post {} was modified concurrently: expected version {}, found {}",
+                id, expected, actual
+            ),
+        }
+    }
+}
+
+impl Error for StoreError {}
+
+impl From<io::Error> for StoreError {
+    fn from(e: io::Error) -> StoreError {
+        StoreError::Io(e)
+    }
+}
+
+pub trait PostRepository {
+    // 保存一篇新文章，返回分配给它的id。slug重复时保存的是追加了序号的slug
+    fn insert(&mut self, post: &Post) -> Result<PostId, StoreError>;
+    // 用post覆盖已有的文章，不检查版本号
+    fn update(&mut self, id: PostId, post: &Post) -> Result<(), StoreError>;
+    // 只有当前版本号等于expected时才写入，返回新的版本号
+    fn update_if(
+        &mut self,
+        id: PostId,
+        expected: Version,
+        post: &Post,
+    ) -> Result<Version, StoreError>;
+    // 文章以及它当前的版本号
+    fn get_versioned(&self, id: PostId) -> Result<(Post, Version), StoreError>;
+
+    fn get(&self, id: PostId) -> Result<Post, StoreError> {
+        self.get_versioned(id).map(|(post, _)| post)
+    }
+    fn remove(&mut self, id: PostId) -> Result<(), StoreError>;
+    // 所有文章的id，从小到大排列
+    fn ids(&self) -> Result<Vec<PostId>, StoreError>;
+}
+
+#[derive(Default)]
+pub struct MemoryRepository {
+    posts: BTreeMap<PostId, (Post, Version)>,
+    next_id: PostId,
+}
+
+impl MemoryRepository {
+    pub fn new() -> MemoryRepository {
+        MemoryRepository::default()
+    }
+
+    // slug改变并且与其他文章重复时，换成第一个没有被使用的候选slug
+    fn with_unique_slug(&self, id: PostId, post: &Post) -> Post {
+        let mut post = post.clone();
+        let previous = self.posts.get(&id).map_or("", |(stored, _)| stored.slug());
+        if !post.meta.slug.is_empty() && post.meta.slug != previous {
+            let slug = metadata::slug_candidates(&post.meta.slug)
+                .find(|slug| {
+                    !self
+                        .posts
+                        .iter()
+                        .any(|(other, (p, _))| *other != id && p.meta.slug == *slug)
+                })
+                .unwrap();
+            post.meta.slug = slug;
+        }
+        post
+    }
+}
+
+impl PostRepository for MemoryRepository {
+    fn insert(&mut self, post: &Post) -> Result<PostId, StoreError> {
+        self.next_id += 1;
+        let post = self.with_unique_slug(self.next_id, post);
+        self.posts.insert(self.next_id, (post, 1));
+        Ok(self.next_id)
+    }
+
+    fn update(&mut self, id: PostId, post: &Post) -> Result<(), StoreError> {
+        let post = self.with_unique_slug(id, post);
+        match self.posts.get_mut(&id) {
+            Some((stored, version)) => {
+                *stored = post;
+                *version += 1;
+                Ok(())
+            }
+            None => Err(StoreError::NotFound(id)),
+        }
+    }
+
+    fn update_if(
+        &mut self,
+        id: PostId,
+        expected: Version,
+        post: &Post,
+    ) -> Result<Version, StoreError> {
+        let post = self.with_unique_slug(id, post);
+        let (stored, version) = self.posts.get_mut(&id).ok_or(StoreError::NotFound(id))?;
+        if *version != expected {
+            return Err(StoreError::Conflict {
+                id,
+                expected,
+                actual: *version,
+            });
+        }
+        *stored = post;
+        *version += 1;
+        Ok(*version)
+    }
+
+    fn get_versioned(&self, id: PostId) -> Result<(Post, Version), StoreError> {
+        self.posts.get(&id).cloned().ok_or(StoreError::NotFound(id))
+    }
+
+    fn remove(&mut self, id: PostId) -> Result<(), StoreError> {
+        self.posts
+            .remove(&id)
+            .map(|_| ())
+            .ok_or(StoreError::NotFound(id))
+    }
+
+    fn ids(&self) -> Result<Vec<PostId>, StoreError> {
+        Ok(self.posts.keys().copied().collect())
+    }
+}
+
+// 等待其他进程释放锁的次数，每次间隔1毫秒
+const LOCK_ATTEMPTS: u32 = 5000;
+
+// 锁只在一次读写期间持有，超过这个秒数的锁属于已经退出的进程，可以直接删除
+const STALE_LOCK_SECS: u64 = 30;
+
+// 临时文件名中的序号，同一进程的多个线程也不会写同一个临时文件
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// 每篇文章保存为 <dir>/<id>.json，版本号保存在文件的 version 字段中。
+// 更新和删除时先创建 <dir>/<id>.lock 作为锁，使多个进程或线程的读取、比较和写入不会交错。
+// 插入时持有 <dir>/repo.lock，下一个id保存在 <dir>/next_id 中，删除的id不会被重新使用。
+// 每个已使用的slug对应一个 <dir>/slugs/<slug> 文件，内容为使用它的文章id；
+// 占用和释放slug时持有 repo.lock，不需要读出所有文章就能判断slug是否重复
+pub struct FileRepository {
+    dir: PathBuf,
+}
+
+impl FileRepository {
+    // 目录不存在时自动创建
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<FileRepository, StoreError> {
+        fs::create_dir_all(dir.as_ref())?;
+        Ok(FileRepository {
+            dir: dir.as_ref().to_path_buf(),
+        })
+    }
+
+    fn path(&self, id: PostId) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    // 先写入临时文件再重命名，避免中途失败留下写了一半的文件。
+    // 临时文件名包含进程号和序号，同时写入的进程和线程互不干扰
+    fn write_atomic(&self, path: &Path, contents: &str) -> Result<(), StoreError> {
+        let mut tmp = path.as_os_str().to_os_string();
+        tmp.push(format!(
+            ".{}.{}.tmp",
+            process::id(),
+            TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let tmp = PathBuf::from(tmp);
+
+        fs::write(&tmp, contents)?;
+        if let Err(e) = fs::rename(&tmp, path) {
+            let _ = fs::remove_file(&tmp);
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    fn write(&self, id: PostId, post: &Post, version: Version) -> Result<(), StoreError> {
+        let mut fields = vec![(String::from("version"), Value::from(version))];
+        fields.extend(to_fields(post));
+        self.write_atomic(&self.path(id), &Value::Object(fields).to_pretty())
+    }
+
+    // 下一个可用的id。旧目录中没有 next_id 文件时从现有的最大id开始
+    fn next_id(&self) -> Result<PostId, StoreError> {
+        let after_last = self.ids()?.last().map_or(1, |last| last + 1);
+        match fs::read_to_string(self.dir.join("next_id")) {
+            Ok(text) => {
+                let next: PostId = text.trim().parse().map_err(|_| {
+                    StoreError::Corrupt(format!("invalid next_id {:?}", text.trim()))
+                })?;
+                Ok(next.max(after_last))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(after_last),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // 取得文章的锁，返回的Lock被丢弃时释放
+    fn lock(&self, id: PostId) -> Result<Lock, StoreError> {
+        Lock::acquire(self.dir.join(format!("{}.lock", id)))
+    }
+
+    // 整个仓库的锁，分配id、插入文章以及占用和释放slug时持有
+    fn lock_repo(&self) -> Result<Lock, StoreError> {
+        Lock::acquire(self.dir.join("repo.lock"))
+    }
+
+    fn slugs_dir(&self) -> PathBuf {
+        self.dir.join("slugs")
+    }
+
+    // 旧目录中没有 slugs 目录时，根据现有文章建立。
+    // 先在临时目录中建立再重命名，中途失败不会留下不完整的索引。调用者持有 repo.lock
+    fn index_slugs(&self) -> Result<(), StoreError> {
+        if self.slugs_dir().is_dir() {
+            return Ok(());
+        }
+        let tmp = self.dir.join(format!(
+            "slugs.{}.{}.tmp",
+            process::id(),
+            TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir(&tmp)?;
+        for id in self.ids()? {
+            let post = self.get(id)?;
+            let marker = tmp.join(post.slug());
+            // 旧数据中重复的slug归较早的文章所有
+            if !post.slug().is_empty() && !marker.exists() {
+                fs::write(marker, format!("{}\n", id))?;
+            }
+        }
+        if let Err(e) = fs::rename(&tmp, self.slugs_dir()) {
+            let _ = fs::remove_dir_all(&tmp);
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    // 为文章id占用slug，slug已被其他文章使用时返回false。
+    // 使用者的文章已经不存在（插入中途失败）时视为空闲。调用者持有 repo.lock
+    fn try_claim(&self, slug: &str, id: PostId) -> Result<bool, StoreError> {
+        let marker = self.slugs_dir().join(slug);
+        match fs::read_to_string(&marker) {
+            Ok(text) => match text.trim().parse::<PostId>() {
+                Ok(owner) if owner == id => return Ok(true),
+                Ok(owner) if self.path(owner).exists() => return Ok(false),
+                _ => {}
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        self.write_atomic(&marker, &format!("{}\n", id))?;
+        Ok(true)
+    }
+
+    // 占用slug或第一个没有被使用的候选slug，返回实际占用的slug
+    fn claim_slug(&self, slug: &str, id: PostId) -> Result<String, StoreError> {
+        self.index_slugs()?;
+        for candidate in metadata::slug_candidates(slug) {
+            if self.try_claim(&candidate, id)? {
+                return Ok(candidate);
+            }
+        }
+        unreachable!()
+    }
+
+    // 释放文章id占用的slug。调用者持有 repo.lock
+    fn release_slug(&self, slug: &str, id: PostId) -> Result<(), StoreError> {
+        if slug.is_empty() {
+            return Ok(());
+        }
+        let marker = self.slugs_dir().join(slug);
+        match fs::read_to_string(&marker) {
+            Ok(text) if text.trim().parse() == Ok(id) => Ok(fs::remove_file(&marker)?),
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // 覆盖已有的文章。slug改变时占用新的slug并释放旧的slug。调用者持有文章的锁
+    fn replace(
+        &self,
+        id: PostId,
+        previous: &Post,
+        post: &Post,
+        version: Version,
+    ) -> Result<(), StoreError> {
+        if post.slug() == previous.slug() {
+            return self.write(id, post, version);
+        }
+        let _lock = self.lock_repo()?;
+        let mut post = post.clone();
+        if !post.meta.slug.is_empty() {
+            post.meta.slug = self.claim_slug(&post.meta.slug, id)?;
+        }
+        self.write(id, &post, version)?;
+        self.release_slug(previous.slug(), id)
+    }
+}
+
+// 锁文件中记录持有者的进程号和取得锁的时间（UNIX秒），如 "pid 1234\ntime 1700000000\n"
+struct Lock {
+    path: PathBuf,
+}
+
+impl Lock {
+    fn acquire(path: PathBuf) -> Result<Lock, StoreError> {
+        for _ in 0..LOCK_ATTEMPTS {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    let lock = Lock { path };
+                    write!(file, "pid {}\ntime {}\n", process::id(), SystemClock.now())?;
+                    return Ok(lock);
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    if Lock::is_stale(&path) {
+                        Lock::break_stale(&path);
+                    } else {
+                        thread::sleep(Duration::from_millis(1))
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let owner = fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| Lock::field(&text, "pid"))
+            .map_or(String::new(), |pid| format!(" by process {}", pid));
+        Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("{} is still locked{}", path.display(), owner),
+        )
+        .into())
+    }
+
+    // 锁文件中记录的时间已超过 STALE_LOCK_SECS。
+    // 刚创建、还没有写入内容的锁文件按修改时间计算
+    fn is_stale(path: &Path) -> bool {
+        let recorded = fs::read_to_string(path)
+            .ok()
+            .and_then(|text| Lock::field(&text, "time"));
+        let locked_at = match recorded {
+            Some(time) => time,
+            None => match fs::metadata(path).and_then(|meta| meta.modified()) {
+                Ok(modified) => modified
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_secs()),
+                // 锁文件已经被删除，重新尝试即可
+                Err(_) => return false,
+            },
+        };
+        SystemClock.now().saturating_sub(locked_at) > STALE_LOCK_SECS
+    }
+
+    // 持有者已经退出，删除锁文件后重新尝试。
+    // 多个进程可能同时发现同一个过期的锁，用 <lock>.break 保证只有一个进程删除它，
+    // 并在删除前再检查一次，避免删掉别的进程刚取得的新锁
+    fn break_stale(path: &Path) {
+        let breaker = path.with_extension("lock.break");
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&breaker)
+        {
+            Ok(_) => {
+                if Lock::is_stale(path) {
+                    let _ = fs::remove_file(path);
+                }
+                let _ = fs::remove_file(&breaker);
+            }
+            // 删除锁的进程在中途退出
+            Err(_) if Lock::is_stale(&breaker) => {
+                let _ = fs::remove_file(&breaker);
+            }
+            Err(_) => thread::sleep(Duration::from_millis(1)),
+        }
+    }
+
+    fn field(text: &str, key: &str) -> Option<u64> {
+        text.lines()
+            .find_map(|line| line.strip_prefix(key)?.strip_prefix(' '))
+            .and_then(|value| value.trim().parse().ok())
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+impl PostRepository for FileRepository {
+    fn insert(&mut self, post: &Post) -> Result<PostId, StoreError> {
+        let _lock = self.lock_repo()?;
+        let id = self.next_id()?;
+        // 先保存下一个id，即使写入文章失败，这个id也不会被再次分配
+        self.write_atomic(&self.dir.join("next_id"), &format!("{}\n", id + 1))?;
+        let mut post = post.clone();
+        if !post.meta.slug.is_empty() {
+            post.meta.slug = self.claim_slug(&post.meta.slug, id)?;
+        }
+        self.write(id, &post, 1)?;
+        Ok(id)
+    }
+
+    fn update(&mut self, id: PostId, post: &Post) -> Result<(), StoreError> {
+        let _lock = self.lock(id)?;
+        let (previous, version) = self.get_versioned(id)?;
+        self.replace(id, &previous, post, version + 1)
+    }
+
+    fn update_if(
+        &mut self,
+        id: PostId,
+        expected: Version,
+        post: &Post,
+    ) -> Result<Version, StoreError> {
+        let _lock = self.lock(id)?;
+        let (previous, actual) = self.get_versioned(id)?;
+        if actual != expected {
+            return Err(StoreError::Conflict {
+                id,
+                expected,
+                actual,
+            });
+        }
+        self.replace(id, &previous, post, actual + 1)?;
+        Ok(actual + 1)
+    }
+
+    fn get_versioned(&self, id: PostId) -> Result<(Post, Version), StoreError> {
+        match fs::read_to_string(self.path(id)) {
+            Ok(text) => {
+                let value = parse(&text)?;
+                // 旧文件中没有版本号
+                let version = optional(&value, "version", Value::as_u64)?.unwrap_or(1);
+                Ok((from_value(&value)?, version))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Err(StoreError::NotFound(id)),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn remove(&mut self, id: PostId) -> Result<(), StoreError> {
+        let _lock = self.lock(id)?;
+        // 无法还原的文章也可以删除，它的slug留给 try_claim 回收
+        let slug = match self.get(id) {
+            Ok(post) => post.slug().to_string(),
+            Err(StoreError::NotFound(id)) => return Err(StoreError::NotFound(id)),
+            Err(_) => String::new(),
+        };
+        fs::remove_file(self.path(id))?;
+        let _repo = self.lock_repo()?;
+        self.release_slug(&slug, id)
+    }
+
+    fn ids(&self) -> Result<Vec<PostId>, StoreError> {
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let name = entry?.file_name();
+            let id = name
+                .to_str()
+                .and_then(|name| name.strip_suffix(".json"))
+                .and_then(|stem| stem.parse().ok());
+            if let Some(id) = id {
+                ids.push(id);
+            }
+        }
+        ids.sort_unstable();
+        Ok(ids)
+    }
+}
+
+// 发布仓库中所有已到发布时间的Scheduled文章，返回被发布的文章id
+pub fn publish_due<R: PostRepository + ?Sized>(
+    repo: &mut R,
+    clock: &dyn Clock,
+) -> Result<Vec<PostId>, StoreError> {
+    let now = clock.now();
+    let mut published = Vec::new();
+
+    for id in repo.ids()? {
+        let (mut post, version) = repo.get_versioned(id)?;
+        if post.state_name() != "scheduled" {
+            continue;
+        }
+        post.tick(now);
+        if post.state_name() == "published" {
+            repo.update_if(id, version, &post)?;
+            published.push(id);
+        }
+    }
+
+    Ok(published)
+}
+
+// 文章序列化为JSON文本
+pub fn encode(post: &Post) -> String {
+    Value::Object(to_fields(post)).to_pretty()
+}
+
+fn to_fields(post: &Post) -> Vec<(String, Value)> {
+    let history = post
+        .history
+        .revisions()
+        .iter()
+        .map(|r| {
+            Value::Object(vec![
+                (String::from("number"), Value::from(r.number as u64)),
+                (String::from("author"), Value::from(r.author.as_str())),
+                (String::from("timestamp"), Value::from(r.timestamp)),
+                (String::from("content"), Value::from(r.content.as_str())),
+            ])
+        })
+        .collect();
+
+    vec![
+        (String::from("title"), Value::from(post.meta.title.as_str())),
+        (String::from("slug"), Value::from(post.meta.slug.as_str())),
+        (String::from("tags"), strings(&post.meta.tags)),
+        (String::from("created"), Value::from(post.meta.created)),
+        (String::from("updated"), Value::from(post.meta.updated)),
+        (
+            String::from("published"),
+            post.meta.published.map_or(Value::Null, Value::from),
+        ),
+        (String::from("author"), Value::from(post.author.as_str())),
+        (
+            String::from("state"),
+            Value::Object(vec![
+                (String::from("name"), Value::from(post.state.name())),
+                (String::from("approvals"), reviewers(&post.approvals)),
+                (
+                    String::from("publish_at"),
+                    post.publish_at.map_or(Value::Null, Value::from),
+                ),
+            ]),
+        ),
+        (String::from("content"), Value::from(post.content.as_str())),
+        (String::from("rejections"), strings(&post.rejections)),
+        (
+            String::from("policy"),
+            Value::Object(vec![
+                (
+                    String::from("required_approvals"),
+                    Value::from(post.policy.required_approvals as u64),
+                ),
+                (
+                    String::from("required_roles"),
+                    strings(&post.policy.required_roles),
+                ),
+                (
+                    String::from("allow_self_approval"),
+                    Value::from(post.policy.allow_self_approval),
+                ),
+            ]),
+        ),
+        (String::from("history"), Value::Array(history)),
+        (String::from("comments"), comments(&post.comments)),
+    ]
+}
+
+// 从JSON文本还原文章，包括状态以及状态相关的批准和发布时间
+pub fn decode(text: &str) -> Result<Post, StoreError> {
+    from_value(&parse(text)?)
+}
+
+fn parse(text: &str) -> Result<Value, StoreError> {
+    json::parse(text)
+        .map_err(|pos| StoreError::Corrupt(format!("invalid JSON at character {}", pos)))
+}
+
+fn from_value(value: &Value) -> Result<Post, StoreError> {
+    let state = field(value, "state")?;
+    let approvals = read_reviewers(field(state, "approvals")?)?;
+    // 旧文件中没有publish_at字段
+    let publish_at = match state.get("publish_at") {
+        None | Some(Value::Null) => None,
+        Some(value) => Some(value.as_u64().ok_or_else(|| corrupt("publish_at"))?),
+    };
+    let name = str_field(state, "name")?;
+    let state = PostState::from_name(name)
+        .ok_or_else(|| StoreError::Corrupt(format!("unknown state {:?}", name)))?;
+
+    let policy = field(value, "policy")?;
+    let policy = ApprovalPolicy {
+        required_approvals: u64_field(policy, "required_approvals")? as usize,
+        required_roles: read_strings(field(policy, "required_roles")?)?,
+        allow_self_approval: field(policy, "allow_self_approval")?
+            .as_bool()
+            .ok_or_else(|| corrupt("allow_self_approval"))?,
+    };
+
+    let mut history = History::new();
+    for revision in array_field(value, "history")? {
+        history.record(
+            str_field(revision, "author")?,
+            u64_field(revision, "timestamp")?,
+            str_field(revision, "content")?,
+        );
+    }
+
+    // 旧文件中没有元数据，创建和修改时间取第一个和最后一个修订版本的时间
+    let meta = Metadata {
+        title: optional(value, "title", |v| v.as_str().map(str::to_string))?.unwrap_or_default(),
+        slug: optional(value, "slug", |v| v.as_str().map(str::to_string))?.unwrap_or_default(),
+        tags: match value.get("tags") {
+            None => Vec::new(),
+            Some(tags) => read_strings(tags)?,
+        },
+        created: optional(value, "created", Value::as_u64)?
+            .or_else(|| history.get(1).map(|r| r.timestamp))
+            .unwrap_or(0),
+        updated: optional(value, "updated", Value::as_u64)?
+            .or_else(|| history.latest().map(|r| r.timestamp))
+            .unwrap_or(0),
+        published: match value.get("published") {
+            None | Some(Value::Null) => None,
+            Some(value) => Some(value.as_u64().ok_or_else(|| corrupt("published"))?),
+        },
+    };
+
+    // 旧文件中没有评论
+    let comments = match value.get("comments") {
+        None => Comments::new(),
+        Some(comments) => read_comments(comments)?,
+    };
+
+    Post::restore(PostParts {
+        state,
+        approvals,
+        publish_at,
+        content: str_field(value, "content")?.to_string(),
+        rejections: read_strings(field(value, "rejections")?)?,
+        author: str_field(value, "author")?.to_string(),
+        policy,
+        history,
+        meta,
+        comments,
+    })
+    .map_err(|e| StoreError::Corrupt(e.to_string()))
+}
+
+fn strings(items: &[String]) -> Value {
+    Value::Array(items.iter().map(|s| Value::from(s.as_str())).collect())
+}
+
+fn reviewers(items: &[Reviewer]) -> Value {
+    Value::Array(
+        items
+            .iter()
+            .map(|r| {
+                Value::Object(vec![
+                    (String::from("name"), Value::from(r.name.as_str())),
+                    (String::from("roles"), strings(&r.roles)),
+                ])
+            })
+            .collect(),
+    )
+}
+
+fn comments(comments: &Comments) -> Value {
+    Value::Array(
+        comments
+            .all()
+            .iter()
+            .map(|c| {
+                Value::Object(vec![
+                    (String::from("id"), Value::from(c.id as u64)),
+                    (String::from("author"), Value::from(c.author.as_str())),
+                    (String::from("text"), Value::from(c.text.as_str())),
+                    (String::from("timestamp"), Value::from(c.timestamp)),
+                    (
+                        String::from("parent"),
+                        c.parent.map_or(Value::Null, |p| Value::from(p as u64)),
+                    ),
+                    (
+                        String::from("lines"),
+                        c.lines.map_or(Value::Null, |l| {
+                            Value::Array(vec![
+                                Value::from(l.start as u64),
+                                Value::from(l.end as u64),
+                            ])
+                        }),
+                    ),
+                    (String::from("resolved"), Value::from(c.resolved)),
+                ])
+            })
+            .collect(),
+    )
+}
+
+fn corrupt(key: &str) -> StoreError {
+    StoreError::Corrupt(format!("missing or invalid field {:?}", key))
+}
+
+// 可以缺省的字段，存在但类型不对时仍然视为损坏
+fn optional<T, F: Fn(&Value) -> Option<T>>(
+    value: &Value,
+    key: &str,
+    read: F,
+) -> Result<Option<T>, StoreError> {
+    match value.get(key) {
+        None => Ok(None),
+        Some(v) => read(v).map(Some).ok_or_else(|| corrupt(key)),
+    }
+}
+
+fn field<'a>(value: &'a Value, key: &str) -> Result<&'a Value, StoreError> {
+    value.get(key).ok_or_else(|| corrupt(key))
+}
+
+fn str_field<'a>(value: &'a Value, key: &str) -> Result<&'a str, StoreError> {
+    field(value, key)?.as_str().ok_or_else(|| corrupt(key))
+}
+
+fn u64_field(value: &Value, key: &str) -> Result<u64, StoreError> {
+    field(value, key)?.as_u64().ok_or_else(|| corrupt(key))
+}
+
+fn array_field<'a>(value: &'a Value, key: &str) -> Result<&'a [Value], StoreError> {
+    field(value, key)?.as_array().ok_or_else(|| corrupt(key))
+}
+
+fn read_strings(value: &Value) -> Result<Vec<String>, StoreError> {
+    value
+        .as_array()
+        .ok_or_else(|| corrupt("list of strings"))?
+        .iter()
+        .map(|item| {
+            item.as_str()
+                .map(str::to_string)
+                .ok_or_else(|| corrupt("list of strings"))
+        })
+        .collect()
+}
+
+fn read_reviewers(value: &Value) -> Result<Vec<Reviewer>, StoreError> {
+    value
+        .as_array()
+        .ok_or_else(|| corrupt("approvals"))?
+        .iter()
+        .map(|item| {
+            Ok(Reviewer {
+                name: str_field(item, "name")?.to_string(),
+                roles: read_strings(field(item, "roles")?)?,
+            })
+        })
+        .collect()
+}
+
+// 评论的id必须从1开始连续编号，回复只能指向更早的评论
+fn read_comments(value: &Value) -> Result<Comments, StoreError> {
+    let items = value.as_array().ok_or_else(|| corrupt("comments"))?;
+    let mut comments = Vec::new();
+
+    for (i, item) in items.iter().enumerate() {
+        let id = u64_field(item, "id")? as usize;
+        if id != i + 1 {
+            return Err(corrupt("id"));
+        }
+        let parent = match field(item, "parent")? {
+            Value::Null => None,
+            parent => match parent.as_u64() {
+                Some(p) if p >= 1 && (p as usize) < id => Some(p as usize),
+                _ => return Err(corrupt("parent")),
+            },
+        };
+        let lines = match field(item, "lines")? {
+            Value::Null => None,
+            lines => match lines.as_array() {
+                Some([start, end]) => Some(LineRange::new(
+                    start.as_u64().ok_or_else(|| corrupt("lines"))? as usize,
+                    end.as_u64().ok_or_else(|| corrupt("lines"))? as usize,
+                )),
+                _ => return Err(corrupt("lines")),
+            },
+        };
+
+        comments.push(Comment {
+            id,
+            author: str_field(item, "author")?.to_string(),
+            text: str_field(item, "text")?.to_string(),
+            timestamp: u64_field(item, "timestamp")?,
+            parent,
+            lines,
+            resolved: field(item, "resolved")?
+                .as_bool()
+                .ok_or_else(|| corrupt("resolved"))?,
+        });
+    }
+
+    Ok(Comments::restore(comments))
+}
@@ -0,0 +1,53 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::ApprovalError;
+
+// 可以对文章执行的状态转换操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    RequestReview,
+    Approve,
+    Reject,
+    Schedule,
+    Archive,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Action::RequestReview => "request review of",
+            Action::Approve => "approve",
+            Action::Reject => "reject",
+            Action::Schedule => "schedule",
+            Action::Archive => "archive",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// 在当前状态下不允许执行的操作
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransitionError {
+    // 操作发生时文章所处的状态
+    pub state: &'static str,
+    pub action: Action,
+    // 状态允许批准，但批准本身被拒绝的原因
+    pub reason: Option<ApprovalError>,
+}
+
+impl fmt::Display for TransitionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "cannot {} a post that is {}", self.action, self.state)?;
+        if let Some(reason) = &self.reason {
+            write!(f, ": {}", reason)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for TransitionError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.reason.as_ref().map(|e| e as &(dyn Error + 'static))
+    }
+}
@@ -0,0 +1,328 @@
+// 与 blog::Post 之间的转换。
+// blog::Post 在运行时记录状态，这里的每个状态是一个单独的类型，
+// 所以从 blog::Post 转换时先得到包含所有可能类型的 AnyPost。
+// new_blog 的类型没有读写作者、批准、审批规则、元数据和评论的方法，
+// 这些数据保存在 Details 中，转换回 blog::Post 时原样恢复。
+// 回到草稿时和 blog::Post 一样清空批准。
+
+use blog::{ApprovalError, ApprovalPolicy, Comments, Metadata, PostParts, PostState, Reviewer};
+
+use crate::{
+    ArchivedPost, ChangesRequestedPost, DraftPost, PendingReviewPost, Post, ScheduledPost,
+};
+
+// 各状态类型把它放在Box中，在Result中传递时不会太大
+pub(crate) struct Details {
+    author: String,
+    approvals: Vec<Reviewer>,
+    policy: ApprovalPolicy,
+    meta: Metadata,
+    comments: Comments,
+    // 审阅中预先安排的发布时间，只在PendingReviewPost中有意义
+    publish_at: Option<u64>,
+}
+
+impl Details {
+    // 与 blog::Post::new() 相同的默认值
+    pub(crate) fn new() -> Box<Details> {
+        let parts = PostParts::default();
+        Box::new(Details {
+            author: parts.author,
+            approvals: parts.approvals,
+            policy: parts.policy,
+            meta: parts.meta,
+            comments: parts.comments,
+            publish_at: None,
+        })
+    }
+
+    pub(crate) fn approvals(&self) -> &[Reviewer] {
+        &self.approvals
+    }
+
+    // 与 blog::Post::approve_by 相同的检查，通过后记下这次批准
+    pub(crate) fn approve(&mut self, reviewer: &Reviewer) -> Result<(), ApprovalError> {
+        if !self.policy.allow_self_approval
+            && !self.author.is_empty()
+            && reviewer.name == self.author
+        {
+            return Err(ApprovalError::SelfApproval(reviewer.name.clone()));
+        }
+        if self.approvals.iter().any(|r| r.name == reviewer.name) {
+            return Err(ApprovalError::AlreadyApproved(reviewer.name.clone()));
+        }
+        let unresolved = self.comments.unresolved().count();
+        if unresolved > 0 {
+            return Err(ApprovalError::UnresolvedComments(unresolved));
+        }
+        self.approvals.push(reviewer.clone());
+        Ok(())
+    }
+
+    // 已有的批准满足审批规则，并且没有未解决的讨论串
+    pub(crate) fn approved(&self) -> bool {
+        self.policy.is_satisfied(&self.approvals) && self.comments.unresolved().next().is_none()
+    }
+
+    // 和 blog::Post 一样记录发布时间
+    pub(crate) fn publish(&mut self, at: u64) {
+        self.publish_at = None;
+        self.meta.published = Some(at);
+    }
+
+    pub(crate) fn into_draft(mut self: Box<Details>) -> Box<Details> {
+        self.approvals.clear();
+        self.publish_at = None;
+        self
+    }
+}
+
+pub enum AnyPost {
+    Draft(DraftPost),
+    PendingReview(PendingReviewPost),
+    Scheduled(ScheduledPost),
+    Published(Post),
+    Archived(ArchivedPost),
+}
+
+impl AnyPost {
+    pub fn state_name(&self) -> &'static str {
+        match self {
+            AnyPost::Draft(_) => "draft",
+            AnyPost::PendingReview(_) => "pending_review",
+            AnyPost::Scheduled(_) => "scheduled",
+            AnyPost::Published(_) => "published",
+            AnyPost::Archived(_) => "archived",
+        }
+    }
+}
+
+impl From<blog::Post> for AnyPost {
+    fn from(post: blog::Post) -> AnyPost {
+        let parts = post.into_parts();
+        let (content, rejections, history) = (parts.content, parts.rejections, parts.history);
+        let details = Box::new(Details {
+            author: parts.author,
+            approvals: parts.approvals,
+            policy: parts.policy,
+            meta: parts.meta,
+            comments: parts.comments,
+            publish_at: parts.publish_at,
+        });
+
+        match parts.state {
+            PostState::Draft => AnyPost::Draft(DraftPost {
+                content,
+                rejections,
+                history,
+                details,
+            }),
+            PostState::PendingReview => AnyPost::PendingReview(PendingReviewPost {
+                content,
+                rejections,
+                history,
+                details,
+            }),
+            PostState::Scheduled => AnyPost::Scheduled(ScheduledPost {
+                content,
+                rejections,
+                history,
+                details,
+                publish_at: parts
+                    .publish_at
+                    .expect("scheduled posts always have publish_at"),
+            }),
+            PostState::Published => AnyPost::Published(Post {
+                content,
+                rejections,
+                history,
+                details,
+            }),
+            PostState::Archived => AnyPost::Archived(ArchivedPost {
+                content,
+                rejections,
+                history,
+                details,
+            }),
+        }
+    }
+}
+
+// new_blog 的状态转换保证了 blog::Post::restore 要求的条件
+impl From<AnyPost> for blog::Post {
+    fn from(post: AnyPost) -> blog::Post {
+        let (state, content, rejections, history, publish_at, details) = match post {
+            AnyPost::Draft(p) => (
+                PostState::Draft,
+                p.content,
+                p.rejections,
+                p.history,
+                None,
+                p.details,
+            ),
+            AnyPost::PendingReview(p) => (
+                PostState::PendingReview,
+                p.content,
+                p.rejections,
+                p.history,
+                p.details.publish_at,
+                p.details,
+            ),
+            AnyPost::Scheduled(p) => (
+                PostState::Scheduled,
+                p.content,
+                p.rejections,
+                p.history,
+                Some(p.publish_at),
+                p.details,
+            ),
+            AnyPost::Published(p) => (
+                PostState::Published,
+                p.content,
+                p.rejections,
+                p.history,
+                None,
+                p.details,
+            ),
+            AnyPost::Archived(p) => (
+                PostState::Archived,
+                p.content,
+                p.rejections,
+                p.history,
+                None,
+                p.details,
+            ),
+        };
+
+        let details = *details;
+        blog::Post::restore(PostParts {
+            state,
+            approvals: details.approvals,
+            publish_at,
+            content,
+            rejections,
+            author: details.author,
+            policy: details.policy,
+            history,
+            meta: details.meta,
+            comments: details.comments,
+        })
+        .expect("new_blog posts are always in a reachable state")
+    }
+}
+
+impl From<DraftPost> for blog::Post {
+    fn from(post: DraftPost) -> blog::Post {
+        AnyPost::Draft(post).into()
+    }
+}
+
+// blog::Post 没有单独的“要求修改”状态，转换后为草稿，要求的修改记为退回理由
+impl From<ChangesRequestedPost> for blog::Post {
+    fn from(mut post: ChangesRequestedPost) -> blog::Post {
+        post.rejections.push(post.changes);
+        AnyPost::Draft(DraftPost {
+            content: post.content,
+            rejections: post.rejections,
+            history: post.history,
+            details: post.details.into_draft(),
+        })
+        .into()
+    }
+}
+
+impl From<PendingReviewPost> for blog::Post {
+    fn from(post: PendingReviewPost) -> blog::Post {
+        AnyPost::PendingReview(post).into()
+    }
+}
+
+impl From<ScheduledPost> for blog::Post {
+    fn from(post: ScheduledPost) -> blog::Post {
+        AnyPost::Scheduled(post).into()
+    }
+}
+
+impl From<Post> for blog::Post {
+    fn from(post: Post) -> blog::Post {
+        AnyPost::Published(post).into()
+    }
+}
+
+impl From<ArchivedPost> for blog::Post {
+    fn from(post: ArchivedPost) -> blog::Post {
+        AnyPost::Archived(post).into()
+    }
+}
+
+// 下面的转换在状态不符时原样返回 blog::Post
+
+impl TryFrom<blog::Post> for DraftPost {
+    type Error = blog::Post;
+
+    fn try_from(post: blog::Post) -> Result<DraftPost, blog::Post> {
+        if post.state() != PostState::Draft {
+            return Err(post);
+        }
+        match AnyPost::from(post) {
+            AnyPost::Draft(p) => Ok(p),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl TryFrom<blog::Post> for PendingReviewPost {
+    type Error = blog::Post;
+
+    fn try_from(post: blog::Post) -> Result<PendingReviewPost, blog::Post> {
+        if post.state() != PostState::PendingReview {
+            return Err(post);
+        }
+        match AnyPost::from(post) {
+            AnyPost::PendingReview(p) => Ok(p),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl TryFrom<blog::Post> for ScheduledPost {
+    type Error = blog::Post;
+
+    fn try_from(post: blog::Post) -> Result<ScheduledPost, blog::Post> {
+        if post.state() != PostState::Scheduled {
+            return Err(post);
+        }
+        match AnyPost::from(post) {
+            AnyPost::Scheduled(p) => Ok(p),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl TryFrom<blog::Post> for Post {
+    type Error = blog::Post;
+
+    fn try_from(post: blog::Post) -> Result<Post, blog::Post> {
+        if post.state() != PostState::Published {
+            return Err(post);
+        }
+        match AnyPost::from(post) {
+            AnyPost::Published(p) => Ok(p),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl TryFrom<blog::Post> for ArchivedPost {
+    type Error = blog::Post;
+
+    fn try_from(post: blog::Post) -> Result<ArchivedPost, blog::Post> {
+        if post.state() != PostState::Archived {
+            return Err(post);
+        }
+        match AnyPost::from(post) {
+            AnyPost::Archived(p) => Ok(p),
+            _ => unreachable!(),
+        }
+    }
+}
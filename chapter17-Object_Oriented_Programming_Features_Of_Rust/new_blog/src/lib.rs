@@ -1,45 +1,554 @@
+mod convert;
+
+pub use convert::AnyPost;
+
+use convert::Details;
+
+use blog::revision::{DiffLine, History, Revision};
+use blog::{Action, Clock, Reviewer, SystemClock, TransitionError};
+
 pub struct Post {
     content: String,
+    rejections: Vec<String>,
+    history: History,
+    // 作者、批准、评论等只在与 blog::Post 转换时用到的数据
+    details: Box<Details>,
 }
 
 pub struct DraftPost {
     content: String,
+    // 每次被退回草稿时审阅者给出的理由
+    rejections: Vec<String>,
+    // 修订记录跟随文章经过每一个状态
+    history: History,
+    details: Box<Details>,
 }
 
 // 将状态转移实现为不同类型之间的转换
 // 直接将实例转化为不同的实例，调用不同的方法
 impl Post {
+    // 返回DraftPost而不是Post，文章在发布之前无法读取content
+    #[allow(clippy::new_ret_no_self)]
     pub fn new() -> DraftPost {
         DraftPost {
             content: String::new(),
+            rejections: Vec::new(),
+            history: History::new(),
+            details: Details::new(),
         }
     }
 
     pub fn content(&self) -> &str {
         &self.content
     }
+
+    pub fn rejections(&self) -> &[String] {
+        &self.rejections
+    }
+
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    // 下线文章，ArchivedPost没有content方法
+    pub fn archive(self) -> ArchivedPost {
+        ArchivedPost {
+            content: self.content,
+            rejections: self.rejections,
+            history: self.history,
+            details: self.details,
+        }
+    }
+
+    // 撤回已发布的文章，重新修改后需要再次审阅
+    pub fn unpublish(self) -> DraftPost {
+        DraftPost {
+            content: self.content,
+            rejections: self.rejections,
+            history: self.history,
+            details: self.details.into_draft(),
+        }
+    }
 }
 
 impl DraftPost {
+    // 匿名追加内容
     pub fn add_text(&mut self, text: &str) {
+        self.edit("", text);
+    }
+
+    // 追加内容，并以author的身份记录一个新的修订版本
+    pub fn edit(&mut self, author: &str, text: &str) {
+        self.edit_with(author, text, &SystemClock);
+    }
+
+    // 与edit相同，修订时间取自clock
+    pub fn edit_with(&mut self, author: &str, text: &str, clock: &dyn Clock) {
         self.content.push_str(text);
+        self.history.record(author, clock.now(), &self.content);
     }
 
     pub fn request_review(self) -> PendingReviewPost {
         PendingReviewPost {
             content: self.content,
+            rejections: self.rejections,
+            history: self.history,
+            details: self.details,
         }
     }
+
+    pub fn rejections(&self) -> &[String] {
+        &self.rejections
+    }
+
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    pub fn revision(&self, number: usize) -> Option<&Revision> {
+        self.history.get(number)
+    }
+
+    pub fn diff(&self, from: usize, to: usize) -> Option<Vec<DiffLine>> {
+        self.history.diff(from, to)
+    }
 }
 
 pub struct PendingReviewPost {
     content: String,
+    rejections: Vec<String>,
+    history: History,
+    details: Box<Details>,
 }
 
 impl PendingReviewPost {
-    pub fn approve(self) -> Post {
-        Post {
+    // 以reviewer的身份批准。与 blog::Post::approve_by 的规则相同：
+    // 作者默认不能批准自己的文章，同一位审阅者只能批准一次，还有未解决的讨论串时不能批准
+    pub fn approve_by(&mut self, reviewer: &Reviewer) -> Result<(), TransitionError> {
+        self.details
+            .approve(reviewer)
+            .map_err(|reason| TransitionError {
+                state: "pending_review",
+                action: Action::Approve,
+                reason: Some(reason),
+            })
+    }
+
+    pub fn approvals(&self) -> &[Reviewer] {
+        self.details.approvals()
+    }
+
+    // 批准满足审批规则后发布，否则原样返回PendingReviewPost
+    pub fn approve(self) -> Result<Post, PendingReviewPost> {
+        self.approve_with(&SystemClock)
+    }
+
+    // 与approve相同，发布时间取自clock
+    pub fn approve_with(mut self, clock: &dyn Clock) -> Result<Post, PendingReviewPost> {
+        if !self.details.approved() {
+            return Err(self);
+        }
+        self.details.publish(clock.now());
+        Ok(Post {
+            content: self.content,
+            rejections: self.rejections,
+            history: self.history,
+            details: self.details,
+        })
+    }
+
+    // 批准满足审批规则后安排在publish_at（UNIX秒）发布，否则原样返回PendingReviewPost
+    pub fn approve_at(self, publish_at: u64) -> Result<ScheduledPost, PendingReviewPost> {
+        if !self.details.approved() {
+            return Err(self);
+        }
+        Ok(ScheduledPost {
+            content: self.content,
+            rejections: self.rejections,
+            history: self.history,
+            details: self.details,
+            publish_at,
+        })
+    }
+
+    // 审阅者可以查看修订记录以及各版本之间的差异
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    // 退回草稿，只有PendingReviewPost才有这个方法
+    pub fn reject(mut self, reason: &str) -> DraftPost {
+        self.rejections.push(reason.to_string());
+        DraftPost {
+            content: self.content,
+            rejections: self.rejections,
+            history: self.history,
+            details: self.details.into_draft(),
+        }
+    }
+
+    // 要求作者按changes修改。与reject不同，修改后只能直接重新提交审阅，
+    // 要求的修改也不算作退回理由
+    pub fn request_changes(self, changes: &str) -> ChangesRequestedPost {
+        ChangesRequestedPost {
+            content: self.content,
+            rejections: self.rejections,
+            changes: changes.to_string(),
+            history: self.history,
+            details: self.details.into_draft(),
+        }
+    }
+}
+
+// 审阅中被要求修改的文章，可以编辑内容，但不能读取content
+pub struct ChangesRequestedPost {
+    content: String,
+    rejections: Vec<String>,
+    // 这次要求的修改
+    changes: String,
+    history: History,
+    details: Box<Details>,
+}
+
+impl ChangesRequestedPost {
+    // 审阅者要求的修改
+    pub fn changes(&self) -> &str {
+        &self.changes
+    }
+
+    pub fn add_text(&mut self, text: &str) {
+        self.edit("", text);
+    }
+
+    pub fn edit(&mut self, author: &str, text: &str) {
+        self.edit_with(author, text, &SystemClock);
+    }
+
+    pub fn edit_with(&mut self, author: &str, text: &str, clock: &dyn Clock) {
+        self.content.push_str(text);
+        self.history.record(author, clock.now(), &self.content);
+    }
+
+    // 修改完成，回到审阅
+    pub fn resubmit(self) -> PendingReviewPost {
+        PendingReviewPost {
             content: self.content,
+            rejections: self.rejections,
+            history: self.history,
+            details: self.details,
         }
     }
+
+    pub fn rejections(&self) -> &[String] {
+        &self.rejections
+    }
+
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    pub fn diff(&self, from: usize, to: usize) -> Option<Vec<DiffLine>> {
+        self.history.diff(from, to)
+    }
+}
+
+pub struct ScheduledPost {
+    content: String,
+    rejections: Vec<String>,
+    history: History,
+    details: Box<Details>,
+    publish_at: u64,
+}
+
+impl ScheduledPost {
+    pub fn publish_at(&self) -> u64 {
+        self.publish_at
+    }
+
+    // 到了发布时间返回Post，否则原样返回ScheduledPost
+    pub fn tick(mut self, now: u64) -> Result<Post, ScheduledPost> {
+        if now >= self.publish_at {
+            self.details.publish(now);
+            Ok(Post {
+                content: self.content,
+                rejections: self.rejections,
+                history: self.history,
+                details: self.details,
+            })
+        } else {
+            Err(self)
+        }
+    }
+
+    // 发布之前仍然可以退回草稿
+    pub fn reject(mut self, reason: &str) -> DraftPost {
+        self.rejections.push(reason.to_string());
+        DraftPost {
+            content: self.content,
+            rejections: self.rejections,
+            history: self.history,
+            details: self.details.into_draft(),
+        }
+    }
+
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+}
+
+// 已下线的文章，不再提供content，只能查看修订记录
+pub struct ArchivedPost {
+    content: String,
+    rejections: Vec<String>,
+    history: History,
+    details: Box<Details>,
+}
+
+impl ArchivedPost {
+    pub fn rejections(&self) -> &[String] {
+        &self.rejections
+    }
+
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blog::{ApprovalError, ApprovalPolicy, Clock, ManualClock};
+
+    // 由bob批准后发布
+    fn publish(mut post: PendingReviewPost) -> Post {
+        post.approve_by(&Reviewer::new("bob")).unwrap();
+        post.approve().ok().unwrap()
+    }
+
+    // 由bob批准后安排在publish_at发布
+    fn schedule(mut post: PendingReviewPost, publish_at: u64) -> ScheduledPost {
+        post.approve_by(&Reviewer::new("bob")).unwrap();
+        post.approve_at(publish_at).ok().unwrap()
+    }
+
+    #[test]
+    fn draft_has_no_rejections() {
+        let post = Post::new();
+        assert!(post.rejections().is_empty());
+    }
+
+    #[test]
+    fn reject_returns_draft_with_reason() {
+        let mut post = Post::new();
+        post.add_text("I ate a salad");
+
+        let mut post = post.request_review().reject("which salad?");
+        assert_eq!(["which salad?"], post.rejections());
+
+        post.add_text(" for lunch today");
+        let post = publish(post.request_review());
+        assert_eq!("I ate a salad for lunch today", post.content());
+        assert_eq!(["which salad?"], post.rejections());
+    }
+
+    #[test]
+    fn edits_use_the_given_clock() {
+        let clock = ManualClock::new(100);
+        let mut post = Post::new();
+        post.edit_with("alice", "one\n", &clock);
+
+        clock.advance(50);
+        let mut post = post.request_review().reject("more");
+        post.edit_with("alice", "two\n", &clock);
+
+        assert_eq!(100, post.revision(1).unwrap().timestamp);
+        assert_eq!(150, post.revision(2).unwrap().timestamp);
+    }
+
+    #[test]
+    fn revisions_survive_review() {
+        let mut post = Post::new();
+        post.edit("alice", "one\n");
+        post.edit("bob", "two\n");
+        assert_eq!("bob", post.revision(2).unwrap().author);
+
+        let mut post = post.request_review().reject("add a third line");
+        post.edit("alice", "three\n");
+
+        let diff: Vec<String> = post
+            .diff(2, 3)
+            .unwrap()
+            .iter()
+            .map(|l| l.to_string())
+            .collect();
+        assert_eq!(vec![" one", " two", "+three"], diff);
+
+        let post = publish(post.request_review());
+        assert_eq!(3, post.history().revisions().len());
+        assert_eq!(
+            "one\ntwo\nthree\n",
+            post.history().latest().unwrap().content
+        );
+    }
+
+    #[test]
+    fn scheduled_post_publishes_when_due() {
+        let clock = ManualClock::new(1_000);
+        let mut post = Post::new();
+        post.add_text("launch day");
+
+        let post = schedule(post.request_review(), 1_060);
+        assert_eq!(1_060, post.publish_at());
+
+        let post = post.tick(clock.now()).err().unwrap();
+        clock.advance(60);
+        let post = post.tick(clock.now()).ok().unwrap();
+        assert_eq!("launch day", post.content());
+
+        let post = post.archive();
+        assert_eq!(1, post.history().revisions().len());
+    }
+
+    #[test]
+    fn request_changes_then_resubmit() {
+        let mut post = Post::new();
+        post.add_text("I ate a salad");
+
+        let mut post = post.request_review().request_changes("say when");
+        assert_eq!("say when", post.changes());
+        post.add_text(" for lunch");
+        let diff: Vec<String> = post
+            .diff(1, 2)
+            .unwrap()
+            .iter()
+            .map(|l| l.to_string())
+            .collect();
+        assert_eq!(vec!["-I ate a salad", "+I ate a salad for lunch"], diff);
+
+        let post = publish(post.resubmit());
+        assert_eq!("I ate a salad for lunch", post.content());
+        // 要求的修改不算作退回
+        assert!(post.rejections().is_empty());
+
+        let mut draft = post.unpublish();
+        draft.add_text(" today");
+        let post = publish(draft.request_review());
+        assert_eq!("I ate a salad for lunch today", post.content());
+    }
+
+    #[test]
+    fn convert_to_and_from_blog_post() {
+        let mut post = Post::new();
+        post.edit("alice", "hello");
+        let scheduled = schedule(post.request_review(), 500);
+
+        let mut shared: blog::Post = scheduled.into();
+        assert_eq!("scheduled", shared.state_name());
+        assert_eq!(Some(500), shared.publish_at());
+        assert_eq!(1, shared.history().revisions().len());
+
+        shared.tick(500);
+        let published = Post::try_from(shared).ok().unwrap();
+        assert_eq!("hello", published.content());
+
+        // 状态不符时原样返回blog::Post
+        let mut reviewed = blog::Post::with_author("alice");
+        reviewed.add_text("draft text");
+        reviewed.request_review();
+        let reviewed = DraftPost::try_from(reviewed).err().unwrap();
+        assert_eq!("alice", reviewed.author());
+
+        let any = AnyPost::from(reviewed);
+        assert_eq!("pending_review", any.state_name());
+        let changes = match any {
+            AnyPost::PendingReview(p) => p.request_changes("more detail"),
+            _ => unreachable!(),
+        };
+        let back: blog::Post = changes.into();
+        assert_eq!("draft", back.state_name());
+        assert_eq!(["more detail"], back.rejections());
+
+        let archived: blog::Post = published.archive().into();
+        assert_eq!("archived", archived.state_name());
+        assert!(ArchivedPost::try_from(archived).is_ok());
+    }
+
+    #[test]
+    fn converted_posts_keep_their_approval_rules() {
+        let mut shared = blog::Post::with_author("alice");
+        shared.set_policy(ApprovalPolicy::approvals(2));
+        shared.add_text("hello");
+        shared.request_review();
+
+        let mut post = PendingReviewPost::try_from(shared.clone()).ok().unwrap();
+        let err = post.approve_by(&Reviewer::new("alice")).unwrap_err();
+        assert_eq!(
+            Some(ApprovalError::SelfApproval(String::from("alice"))),
+            err.reason
+        );
+        post.approve_by(&Reviewer::new("bob")).unwrap();
+        assert!(post.approve_by(&Reviewer::new("bob")).is_err());
+
+        // 还差一位审阅者，不能发布也不能安排发布
+        let post = post.approve().err().unwrap();
+        let mut post = post.approve_at(10).err().unwrap();
+        post.approve_by(&Reviewer::new("carol")).unwrap();
+        let post = post.approve_with(&ManualClock::new(700)).ok().unwrap();
+
+        let published = blog::Post::from(post);
+        assert_eq!("published", published.state_name());
+        assert_eq!(Some(700), published.published());
+        assert_eq!(2, published.approvals().len());
+
+        // 未解决的讨论串会阻止批准
+        shared.comment("bob", "typo", None).unwrap();
+        let mut post = PendingReviewPost::try_from(shared).ok().unwrap();
+        let err = post.approve_by(&Reviewer::new("bob")).unwrap_err();
+        assert_eq!(Some(ApprovalError::UnresolvedComments(1)), err.reason);
+        assert!(post.approvals().is_empty());
+    }
+
+    // blog::Post 的各部分在转换前后完全相同
+    fn assert_round_trip(post: blog::Post) {
+        let before = post.clone().into_parts();
+        let after = blog::Post::from(AnyPost::from(post)).into_parts();
+        assert_eq!(before.state, after.state);
+        assert_eq!(before.approvals, after.approvals);
+        assert_eq!(before.publish_at, after.publish_at);
+        assert_eq!(before.content, after.content);
+        assert_eq!(before.rejections, after.rejections);
+        assert_eq!(before.author, after.author);
+        assert_eq!(before.policy, after.policy);
+        assert_eq!(before.history, after.history);
+        assert_eq!(before.meta, after.meta);
+        assert_eq!(before.comments, after.comments);
+    }
+
+    #[test]
+    fn blog_post_round_trips_through_new_blog() {
+        let mut post = blog::Post::with_author("alice");
+        post.set_policy(blog::ApprovalPolicy::approvals(2));
+        post.set_title("Lunch");
+        post.add_tag("food");
+        post.add_text("I ate a salad\n");
+        assert_round_trip(post.clone());
+
+        post.request_review();
+        post.schedule(900);
+        let comment = post.comment("bob", "which salad?", None).unwrap();
+        post.resolve_comment(comment, "alice").unwrap();
+        post.approve_by(&blog::Reviewer::new("bob")).unwrap();
+        assert_eq!("pending_review", post.state_name());
+        assert_round_trip(post.clone());
+
+        post.approve_by(&blog::Reviewer::new("carol")).unwrap();
+        assert_eq!("scheduled", post.state_name());
+        assert_round_trip(post.clone());
+
+        post.tick(900);
+        assert_round_trip(post.clone());
+
+        post.archive();
+        assert_round_trip(post);
+    }
 }
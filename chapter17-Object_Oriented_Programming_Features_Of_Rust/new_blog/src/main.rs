@@ -1,3 +1,4 @@
+use blog::Reviewer;
 use new_blog::Post;
 
 fn main() {
@@ -5,9 +6,10 @@ fn main() {
 
     post.add_text("I ate a salad for lunch today");
 
-    let post = post.request_review();
+    let mut post = post.request_review();
 
-    let post = post.approve();
+    post.approve_by(&Reviewer::new("bob")).unwrap();
+    let post = post.approve().ok().unwrap();
 
     assert_eq!("I ate a salad for lunch today", post.content());
-}
\ No newline at end of file
+}